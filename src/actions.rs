@@ -0,0 +1,357 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::backend::{BackendError, WindowInfo, WindowLister};
+use crate::config::{KillScope, SessionAction};
+use crate::misc::{fmt_time, send_stop_warning};
+use crate::process::{ProcessHandle, app_scope_cgroup, cgroup_pids, descendants};
+use crate::session::end_session;
+
+/// How often a terminated process is checked during the grace period.
+const EXIT_POLL: Duration = Duration::from_millis(250);
+
+/// Trait that defines the "interface" for what the monitor does to the
+/// user's session once a rule fires.
+pub trait Actions {
+    /// Ask the process (and what `policy.scope` adds) to terminate; force
+    /// it after `policy.grace`. `reason` is what the log says it is for.
+    fn terminate(&self, process: &ProcessHandle, policy: KillPolicy, reason: &str);
+    /// Suspend the process (and what `scope` adds) without terminating it;
+    /// returns whether it was frozen.
+    fn freeze(&self, process: &ProcessHandle, scope: KillScope, reason: &str) -> bool;
+    /// Let a frozen process (its tree or app scope) run again, if `pid` is
+    /// still the process that started at `start_ticks`.
+    fn resume(&self, pid: u32, start_ticks: u64);
+    /// Ask the window to close through the backend that listed it.
+    fn close(
+        &self,
+        lister: &dyn WindowLister,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError>;
+    /// Tell the user that their time is almost up.
+    fn warn(&self, user: &str, remaining: i64) -> Result<()>;
+    /// Lock or terminate the user's whole graphical session.
+    fn end_session(&self, user: &str, action: SessionAction) -> Result<()>;
+}
+
+/// How a termination is carried out.
+#[derive(Debug, Clone, Copy)]
+pub struct KillPolicy {
+    pub grace: Duration,
+    pub scope: KillScope,
+}
+
+/* -------------------------------------------------------------------------- */
+/* Implementation acting on the live system                                   */
+/* -------------------------------------------------------------------------- */
+#[derive(Default, Clone)]
+pub struct SystemActions {
+    /// Pids whose termination is still in progress, so the next scans do not
+    /// start another one.
+    pending: Arc<Mutex<HashSet<u32>>>,
+}
+
+impl Actions for SystemActions {
+    fn terminate(&self, process: &ProcessHandle, policy: KillPolicy, reason: &str) {
+        let pid = process.pid();
+        let process = match process.try_clone() {
+            Ok(process) => process,
+            Err(e) => {
+                println!("Failed to hold on to process {pid}: {e}");
+                return;
+            }
+        };
+        if !self.pending.lock().unwrap().insert(pid) {
+            return;
+        }
+        // Waiting out the grace period must not hold up the scans.
+        let pending = Arc::clone(&self.pending);
+        let reason = reason.to_string();
+        thread::spawn(move || {
+            terminate_with_grace(&process, policy, &reason);
+            pending.lock().unwrap().remove(&pid);
+        });
+    }
+
+    fn freeze(&self, process: &ProcessHandle, scope: KillScope, reason: &str) -> bool {
+        let pid = process.pid();
+        if process.has_exited() {
+            println!("Process {pid} already exited");
+            return false;
+        }
+        if scope == KillScope::Cgroup {
+            match app_scope_cgroup(pid) {
+                Some(dir) if !only_owned_by(&dir, process.uid()) => println!(
+                    "{} also runs processes of other users, stopping the tree of {pid}",
+                    dir.display()
+                ),
+                Some(dir) => {
+                    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+                    match fs::write(dir.join("cgroup.freeze"), "1") {
+                        Ok(()) => {
+                            println!("Froze {name} ({reason})");
+                            return true;
+                        }
+                        Err(e) => println!("Failed to freeze {name} ({e}), stopping its tree"),
+                    }
+                }
+                None => println!("Process {pid} does not run in an app scope, stopping its tree"),
+            }
+        }
+
+        let targets = collect_targets(process, process.uid(), scope, None);
+        for target in &targets {
+            if let Err(e) = target.signal(libc::SIGSTOP) {
+                println!("Failed to send SIGSTOP to {}: {e}", target.pid());
+            }
+        }
+        println!(
+            "Sent SIGSTOP to process {pid} and {} descendants ({reason})",
+            targets.len().saturating_sub(1)
+        );
+        true
+    }
+
+    fn resume(&self, pid: u32, start_ticks: u64) {
+        let Ok(process) = ProcessHandle::open_started(pid, start_ticks) else {
+            println!("Frozen process {pid} is gone");
+            return;
+        };
+        // Thaw whatever was frozen, whichever scope was configured then.
+        if let Some(dir) = app_scope_cgroup(pid) {
+            let freeze = dir.join("cgroup.freeze");
+            if fs::read_to_string(&freeze).is_ok_and(|frozen| frozen.trim() == "1")
+                && let Err(e) = fs::write(&freeze, "0")
+            {
+                println!("Failed to thaw {}: {e}", dir.display());
+            }
+        }
+        let targets = collect_targets(&process, process.uid(), KillScope::Tree, None);
+        for target in &targets {
+            if let Err(e) = target.signal(libc::SIGCONT) {
+                println!("Failed to send SIGCONT to {}: {e}", target.pid());
+            }
+        }
+        println!(
+            "Resumed process {pid} and {} descendants",
+            targets.len().saturating_sub(1)
+        );
+    }
+
+    fn close(
+        &self,
+        lister: &dyn WindowLister,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        lister.close_window(user, backend_path, win)
+    }
+
+    fn warn(&self, user: &str, remaining: i64) -> Result<()> {
+        send_stop_warning(user, remaining)
+    }
+
+    fn end_session(&self, user: &str, action: SessionAction) -> Result<()> {
+        Ok(end_session(user, action)?)
+    }
+}
+
+/// SIGTERM, wait up to the grace period for the processes to exit, then
+/// SIGKILL. Every signal goes through a pidfd, so a process that exited
+/// meanwhile cannot be confused with a new one that reused its pid.
+fn terminate_with_grace(process: &ProcessHandle, policy: KillPolicy, reason: &str) {
+    let pid = process.pid();
+    if process.has_exited() {
+        println!("Process {pid} already exited");
+        return;
+    }
+    let cgroup = match policy.scope {
+        KillScope::Cgroup => {
+            let cgroup = app_scope_cgroup(pid);
+            if cgroup.is_none() {
+                println!("Process {pid} does not run in an app scope, terminating its tree");
+            }
+            cgroup
+        }
+        KillScope::Process | KillScope::Tree => None,
+    };
+
+    let mut targets = collect_targets(process, process.uid(), policy.scope, cgroup.as_deref());
+    let what = match (&cgroup, policy.scope) {
+        (Some(dir), _) => format!(
+            "{} processes in {}",
+            targets.len(),
+            dir.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        (None, KillScope::Process) => format!("process {pid}"),
+        (None, _) => format!(
+            "process {pid} and {} descendants",
+            targets.len().saturating_sub(1)
+        ),
+    };
+    for target in &targets {
+        if let Err(e) = target.signal(libc::SIGTERM) {
+            println!("Failed to send SIGTERM to {}: {e}", target.pid());
+        }
+    }
+    println!("Sent SIGTERM to {what} ({reason})");
+
+    let all_exited = |targets: &[ProcessHandle]| targets.iter().all(|target| target.has_exited());
+    let deadline = Instant::now() + policy.grace;
+    while Instant::now() < deadline && !all_exited(&targets) {
+        thread::sleep(EXIT_POLL);
+    }
+    if all_exited(&targets) {
+        println!("{what} exited after SIGTERM");
+        return;
+    }
+
+    // Catch children spawned in the meantime (e.g. a respawned window).
+    for target in collect_targets(process, process.uid(), policy.scope, cgroup.as_deref()) {
+        let known = targets
+            .iter()
+            .any(|t| t.pid() == target.pid() && t.start_ticks() == target.start_ticks());
+        if !known {
+            targets.push(target);
+        }
+    }
+    let survivors: Vec<&ProcessHandle> = targets
+        .iter()
+        .filter(|target| !target.has_exited())
+        .collect();
+    println!(
+        "{:?} ignored SIGTERM for {}, sending SIGKILL",
+        survivors.iter().map(|t| t.pid()).collect::<Vec<_>>(),
+        fmt_time(policy.grace.as_secs() as i64)
+    );
+
+    // `cgroup.kill` (Linux 5.14+) also gets processes forked while killing,
+    // but must not reach processes of other users (e.g. started with sudo).
+    if let Some(dir) = &cgroup
+        && only_owned_by(dir, process.uid())
+    {
+        match fs::write(dir.join("cgroup.kill"), "1") {
+            Ok(()) => {
+                println!("Killed {what}");
+                return;
+            }
+            Err(e) => println!("Failed to write cgroup.kill ({e}), killing one by one"),
+        }
+    }
+    for target in survivors {
+        match target.signal(libc::SIGKILL) {
+            Ok(()) => println!("Killed {}", target.pid()),
+            Err(e) => println!("Failed to send SIGKILL to {}: {e}", target.pid()),
+        }
+    }
+}
+
+/// The processes a termination of `process` covers, each pinned by its own
+/// pidfd. `process` itself comes first; other processes are only included
+/// when they are owned by `uid`.
+fn collect_targets(
+    process: &ProcessHandle,
+    uid: Option<u32>,
+    scope: KillScope,
+    cgroup: Option<&Path>,
+) -> Vec<ProcessHandle> {
+    let mut targets = Vec::new();
+    let mut pids = Vec::new();
+    if !process.has_exited() {
+        if let Ok(handle) = process.try_clone() {
+            targets.push(handle);
+        }
+        match (scope, cgroup) {
+            (_, Some(dir)) => pids.extend(cgroup_pids(dir)),
+            (KillScope::Process, None) => {}
+            (_, None) => pids.extend(descendants(process.pid())),
+        }
+    } else if let Some(dir) = cgroup {
+        pids.extend(cgroup_pids(dir));
+    }
+
+    for target in pids {
+        // Never hit init or ourselves, whatever the cgroup contains.
+        if target <= 1 || target == std::process::id() || targets.iter().any(|t| t.pid() == target)
+        {
+            continue;
+        }
+        if let Ok(handle) = ProcessHandle::open(target)
+            && uid.is_some()
+            && handle.uid() == uid
+        {
+            targets.push(handle);
+        }
+    }
+    targets
+}
+
+/// Whether every process in the cgroup `dir` is owned by `uid`, so the
+/// whole cgroup may be frozen or killed at once.
+fn only_owned_by(dir: &Path, uid: Option<u32>) -> bool {
+    uid.is_some()
+        && cgroup_pids(dir).into_iter().all(|pid| {
+            // Processes that exited meanwhile do not matter.
+            ProcessHandle::open(pid).map_or(true, |handle| handle.uid() == uid)
+        })
+}
+
+/* -------------------------------------------------------------------------- */
+/* Implementation that only reports (replays and observe mode)                */
+/* -------------------------------------------------------------------------- */
+pub struct LoggedActions;
+
+impl Actions for LoggedActions {
+    fn terminate(&self, process: &ProcessHandle, policy: KillPolicy, reason: &str) {
+        let pid = process.pid();
+        println!(
+            "Would kill pid {pid} ({reason}; {:?} scope, SIGKILL after {})",
+            policy.scope,
+            fmt_time(policy.grace.as_secs() as i64)
+        );
+    }
+
+    fn freeze(&self, process: &ProcessHandle, scope: KillScope, reason: &str) -> bool {
+        let pid = process.pid();
+        println!("Would freeze pid {pid} ({reason}; {scope:?} scope)");
+        // Nothing is frozen, so nothing must wait to be resumed.
+        false
+    }
+
+    fn resume(&self, pid: u32, _start_ticks: u64) {
+        println!("Would resume pid {pid}");
+    }
+
+    fn close(
+        &self,
+        _lister: &dyn WindowLister,
+        _user: &str,
+        _backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        println!("Would close window '{}' of {}", win.title, win.pid);
+        Ok(())
+    }
+
+    fn warn(&self, user: &str, remaining: i64) -> Result<()> {
+        println!("Would warn {user}: 'Stopping in {}'", fmt_time(remaining));
+        Ok(())
+    }
+
+    fn end_session(&self, user: &str, action: SessionAction) -> Result<()> {
+        let verb = match action {
+            SessionAction::Lock => "lock",
+            SessionAction::Terminate => "terminate",
+        };
+        println!("Would {verb} the session of {user}");
+        Ok(())
+    }
+}
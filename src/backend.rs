@@ -1,17 +1,81 @@
-use crate::misc::run_as_user;
+use crate::misc::{
+    process_command, process_name, run_as_user, user_pids, user_session_env, user_uid,
+};
+use crate::session::graphical_session;
+use regex::Regex;
+use std::fmt;
+use std::fs;
 use std::io::{self};
+use std::path::Path;
 use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+mod error;
+mod exec;
+mod hyprland;
+mod kwin;
+mod replay;
+mod sway;
+mod wayland;
+mod x11;
+
+pub use error::BackendError;
+pub use exec::ExecLister;
+pub use hyprland::HyprlandLister;
+pub use kwin::{KwinLister, close_kwin_window, query_kwin_windows};
+pub use replay::ReplayLister;
+pub use sway::SwayLister;
+pub use wayland::WaylandLister;
+pub use x11::{X11Lister, x11_idle_millis};
+
+#[derive(Debug, Clone, Default)]
 pub struct WindowInfo {
     pub title: String,
     pub pid: u32,
+    /// Backend specific window id (X11 window, niri/sway container id,
+    /// Hyprland address, ...).
+    pub id: Option<String>,
+    /// Wayland app_id or the class part of X11 `WM_CLASS`.
+    pub app_id: Option<String>,
+    pub workspace: Option<String>,
+    /// `None` when the backend cannot tell.
+    pub fullscreen: Option<bool>,
+    /// `None` when the backend cannot tell.
+    pub focused: Option<bool>,
+    /// Minimised or otherwise not shown; `None` when the backend cannot tell.
+    pub hidden: Option<bool>,
+    /// Which backend of a backend list reported the window.
+    pub backend: Option<Backend>,
 }
 
 /// Trait that defines the "interface" for listing windows.
 pub trait WindowLister {
-    /// Returns a list of windows (title + pid) or an error.
-    fn list_windows(&self, user: &str, backend_path: &str) -> io::Result<Vec<WindowInfo>>;
+    /// Returns a list of windows (title, pid and whatever metadata the
+    /// backend knows) or an error.
+    fn list_windows(&self, user: &str, backend_path: &str)
+    -> Result<Vec<WindowInfo>, BackendError>;
+
+    /// Ask the compositor to close `win` like its close button would, so the
+    /// application may still ask to save.
+    fn close_window(
+        &self,
+        _user: &str,
+        _backend_path: &str,
+        _win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        Err(BackendError::Missing(
+            "this backend cannot close windows".to_string(),
+        ))
+    }
+}
+
+/// The backend specific id of `win`, required to close it.
+fn window_id(win: &WindowInfo) -> Result<&str, BackendError> {
+    win.id.as_deref().ok_or_else(|| {
+        BackendError::Missing(format!(
+            "the window of {} has no id to close it by",
+            win.pid
+        ))
+    })
 }
 
 /* -------------------------------------------------------------------------- */
@@ -20,37 +84,111 @@ pub trait WindowLister {
 pub struct KdotoolLister;
 
 impl WindowLister for KdotoolLister {
-    fn list_windows(&self, user: &str, backend_path: &str) -> io::Result<Vec<WindowInfo>> {
+    fn list_windows(
+        &self,
+        user: &str,
+        backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
         let exec_path = if backend_path.is_empty() {
             "kdotool"
         } else {
             backend_path
         };
 
-        let output = run_as_user(user, &[exec_path, "search", "--name", "."]).unwrap_or_else(|e| {
-            eprintln!("failed to execute kdotool: {}", e);
-            String::default()
-        });
+        let output = run_as_user(user, &[exec_path, "search", "--name", "."])
+            .map_err(|e| BackendError::from_command("kdotool", e))?;
+        let ids: Vec<&str> = output.lines().filter(|id| !id.is_empty()).collect();
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
 
-        let mut result = Vec::new();
-        for win_id in output.lines() {
-            // Resolve the real PID belonging to the window.
-            let pid_str = run_as_user(&user, &[exec_path, "getwindowpid", win_id]).unwrap();
-            let pid: u32 = match pid_str.trim().parse() {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
+        // Every kdotool invocation loads a KWin script, so query all windows
+        // and the active one with one chained command instead of three calls
+        // per window.
+        let mut args = vec![exec_path];
+        for id in &ids {
+            args.extend([
+                "getwindowpid",
+                id,
+                "getwindowclassname",
+                id,
+                "getwindowname",
+                id,
+            ]);
+        }
+        args.push("getactivewindow");
+        // The ids were valid a moment ago, so a failure here most likely
+        // means one of the windows was closed in between.
+        let details = run_as_user(user, &args)
+            .map_err(|e| BackendError::WindowVanished(format!("{:#}", e)))?;
 
-            // Obtain the (potentially refreshed) window title.
-            let title = run_as_user(&user, &[exec_path, "getwindowname", win_id]).unwrap();
+        Ok(parse_kdotool_windows(&ids, &details))
+    }
 
+    fn close_window(
+        &self,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        let exec_path = if backend_path.is_empty() {
+            "kdotool"
+        } else {
+            backend_path
+        };
+        run_as_user(user, &[exec_path, "windowclose", window_id(win)?])
+            .map_err(|e| BackendError::from_command("kdotool", e))?;
+        Ok(())
+    }
+}
+
+/// Turn the output of the chained kdotool call (pid, class and title of each
+/// of `ids`, then the active window) into windows. Every command prints one
+/// line, except that titles may span several lines and `run_as_user` trims
+/// trailing empty lines (e.g. the empty title of the last window).
+fn parse_kdotool_windows(ids: &[&str], output: &str) -> Vec<WindowInfo> {
+    let mut lines: Vec<&str> = output.lines().collect();
+    let active = match lines.last() {
+        Some(last) if ids.contains(&last.trim()) => lines.pop().map(str::trim),
+        _ => None,
+    };
+
+    // Lines that continue a multi-line title.
+    let mut extra = lines.len().saturating_sub(ids.len() * 3);
+    let mut rest = &lines[..];
+    let mut result = Vec::new();
+    for (i, win_id) in ids.iter().enumerate() {
+        let field = |n: usize| rest.get(n).map_or("", |line| line.trim());
+        let mut title_lines = 1;
+        if i + 1 == ids.len() {
+            title_lines += extra;
+        } else {
+            // The title ends where the pid of the next window starts.
+            while extra > 0 && field(2 + title_lines).parse::<u32>().is_err() {
+                title_lines += 1;
+                extra -= 1;
+            }
+        }
+        let pid = field(0).parse::<u32>();
+        let class = field(1).to_string();
+        let title = rest
+            .get(2..(2 + title_lines).min(rest.len()))
+            .unwrap_or_default()
+            .join("\n");
+        rest = rest.get(2 + title_lines..).unwrap_or_default();
+
+        if let Ok(pid) = pid {
             result.push(WindowInfo {
                 title: title.trim().to_string(),
                 pid,
+                id: Some(win_id.to_string()),
+                app_id: Some(class).filter(|c| !c.is_empty()),
+                focused: active.map(|a| a == *win_id),
+                ..Default::default()
             });
         }
-        Ok(result)
     }
+    result
 }
 
 /* -------------------------------------------------------------------------- */
@@ -59,7 +197,11 @@ impl WindowLister for KdotoolLister {
 pub struct NiriLister;
 
 impl WindowLister for NiriLister {
-    fn list_windows(&self, user: &str, backend_path: &str) -> io::Result<Vec<WindowInfo>> {
+    fn list_windows(
+        &self,
+        user: &str,
+        backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
         let exec_path = if backend_path.is_empty() {
             "niri"
         } else {
@@ -67,31 +209,125 @@ impl WindowLister for NiriLister {
         };
 
         // `niri msg windows` returns JSON describing each window.
-        let output = run_as_user(user, &[exec_path, "msg", "-j", "windows"]).unwrap_or_else(|e| {
-            eprintln!("failed to execute niri: {}", e);
-            String::default()
-        });
+        let output = run_as_user(user, &[exec_path, "msg", "-j", "windows"])
+            .map_err(|e| BackendError::from_command("niri", e))?;
 
         #[derive(serde::Deserialize)]
         struct NiriWindow {
+            id: u64,
             pid: u32,
             title: String,
+            app_id: Option<String>,
+            workspace_id: Option<u64>,
+            is_focused: bool,
         }
 
-        let parsed: Vec<NiriWindow> = serde_json::from_str(&output).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("failed to parse niri JSON: {}", e),
-            )
-        })?;
+        let parsed: Vec<NiriWindow> = serde_json::from_str(&output)
+            .map_err(|e| BackendError::Parse(format!("failed to parse niri JSON: {}", e)))?;
 
-        Ok(parsed
+        let mut windows: Vec<WindowInfo> = parsed
             .into_iter()
             .map(|w| WindowInfo {
                 pid: w.pid,
                 title: w.title,
+                id: Some(w.id.to_string()),
+                app_id: w.app_id,
+                workspace: w.workspace_id.map(|id| id.to_string()),
+                fullscreen: None,
+                focused: Some(w.is_focused),
+                hidden: None,
+                backend: None,
             })
-            .collect())
+            .collect();
+        resolve_xwayland_satellite(user, &mut windows);
+        Ok(windows)
+    }
+
+    fn close_window(
+        &self,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        let exec_path = if backend_path.is_empty() {
+            "niri"
+        } else {
+            backend_path
+        };
+        let id = window_id(win)?;
+        run_as_user(
+            user,
+            &[exec_path, "msg", "action", "close-window", "--id", id],
+        )
+        .map_err(|e| BackendError::from_command("niri", e))?;
+        Ok(())
+    }
+}
+
+/// niri reports every X11 window with the pid of `xwayland-satellite`. Look
+/// those windows up on the satellite's X display and substitute the pid of
+/// the real client, so enforcement does not hit all X11 apps at once.
+/// Windows that cannot be attributed to exactly one client are dropped.
+fn resolve_xwayland_satellite(user: &str, windows: &mut Vec<WindowInfo>) {
+    let mut satellites: Vec<u32> = windows
+        .iter()
+        .map(|w| w.pid)
+        .filter(|&pid| process_name(pid).as_deref() == Some("xwayland-satellite"))
+        .collect();
+    satellites.sort_unstable();
+    satellites.dedup();
+
+    for satellite in satellites {
+        // The display is passed as the first argument (e.g. `:0`); fall back
+        // to the session's `DISPLAY`.
+        let display = fs::read(format!("/proc/{satellite}/cmdline"))
+            .unwrap_or_default()
+            .split(|&b| b == 0)
+            .map(String::from_utf8_lossy)
+            .find(|arg| arg.starts_with(':'))
+            .map(|arg| arg.into_owned())
+            .unwrap_or_default();
+
+        let x_windows = match x11::connect(user, &display)
+            .and_then(|(conn, screen)| x11::list_client_windows(&conn, screen))
+        {
+            Ok(x_windows) => x_windows,
+            Err(e) => {
+                eprintln!("failed to query xwayland-satellite display: {}", e);
+                Vec::new()
+            }
+        };
+
+        windows.retain_mut(|win| {
+            if win.pid != satellite {
+                return true;
+            }
+            let mut pids: Vec<u32> = x_windows
+                .iter()
+                .filter(|x| x.title == win.title)
+                .map(|x| x.pid)
+                .collect();
+            // Several windows of one client may share a title (e.g. Steam).
+            pids.sort_unstable();
+            pids.dedup();
+            match pids[..] {
+                [pid] => {
+                    win.pid = pid;
+                    true
+                }
+                [] => {
+                    eprintln!("no X11 client found for '{}', ignoring it", win.title);
+                    false
+                }
+                _ => {
+                    eprintln!(
+                        "X11 clients {:?} all have a window titled '{}', ignoring it",
+                        pids, win.title
+                    );
+                    false
+                }
+            }
+        });
     }
 }
 
@@ -101,7 +337,11 @@ impl WindowLister for NiriLister {
 pub struct XdotoolLister;
 
 impl WindowLister for XdotoolLister {
-    fn list_windows(&self, user: &str, backend_path: &str) -> io::Result<Vec<WindowInfo>> {
+    fn list_windows(
+        &self,
+        user: &str,
+        backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
         let exec_path = if backend_path.is_empty() {
             "xdotool"
         } else {
@@ -109,37 +349,107 @@ impl WindowLister for XdotoolLister {
         };
 
         let output = run_as_user(user, &[exec_path, "search", "--onlyvisible", "--name", "."])
-            .unwrap_or_else(|e| {
-                eprintln!("failed to execute xdotool: {}", e);
-                String::default()
-            });
+            .map_err(|e| BackendError::from_command("xdotool", e))?;
+
+        let active = run_as_user(user, &[exec_path, "getactivewindow"]).ok();
 
         let mut result = Vec::new();
         for win_id in output.lines() {
-            // Resolve the real PID belonging to the window.
-            let pid_str = run_as_user(&user, &[exec_path, "getwindowpid", win_id]).unwrap();
+            // Resolve the real PID belonging to the window. A window closed
+            // since the search is skipped; it is gone for the next scan.
+            let Ok(pid_str) = run_as_user(user, &[exec_path, "getwindowpid", win_id]) else {
+                continue;
+            };
             let pid: u32 = match pid_str.trim().parse() {
                 Ok(v) => v,
                 Err(_) => continue,
             };
 
             // Obtain the (potentially refreshed) window title.
-            let title = run_as_user(&user, &[exec_path, "getwindowname", win_id]).unwrap();
+            let Ok(title) = run_as_user(user, &[exec_path, "getwindowname", win_id]) else {
+                continue;
+            };
+            let class = run_as_user(user, &[exec_path, "getwindowclassname", win_id]).ok();
 
             result.push(WindowInfo {
                 title: title.trim().to_string(),
                 pid,
+                id: Some(win_id.to_string()),
+                app_id: class.filter(|c| !c.is_empty()),
+                focused: active.as_ref().map(|a| a.trim() == win_id),
+                ..Default::default()
             });
         }
         Ok(result)
     }
+
+    fn close_window(
+        &self,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        let exec_path = if backend_path.is_empty() {
+            "xdotool"
+        } else {
+            backend_path
+        };
+        run_as_user(user, &[exec_path, "windowclose", window_id(win)?])
+            .map_err(|e| BackendError::from_command("xdotool", e))?;
+        Ok(())
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/* Implementation without any window system (scans /proc)                    */
+/* -------------------------------------------------------------------------- */
+/// Reports every process of the user whose command line matches
+/// `cmd_pattern` as if it were a window (with an empty title).
+pub struct ProcLister {
+    pub cmd_regex: Option<Regex>,
+}
+
+impl WindowLister for ProcLister {
+    fn list_windows(
+        &self,
+        user: &str,
+        _backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
+        let Some(cmd_regex) = &self.cmd_regex else {
+            return Ok(Vec::new());
+        };
+        let uid = user_uid(user).map_err(|e| BackendError::Other(io::Error::other(e)))?;
+
+        let mut result = Vec::new();
+        for pid in user_pids(uid) {
+            let Some(command) = process_command(pid) else {
+                continue;
+            };
+            if !command.is_empty() && cmd_regex.is_match(&command) {
+                result.push(WindowInfo {
+                    title: String::new(),
+                    pid,
+                    app_id: process_name(pid),
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(result)
+    }
 }
 
-#[derive(Clone, Debug, clap::ValueEnum)]
+#[derive(Clone, Debug, PartialEq, clap::ValueEnum)]
 pub enum Backend {
     Kdotool,
+    Kwin,
     Niri,
     Xdotool,
+    X11,
+    Sway,
+    Hyprland,
+    Wayland,
+    Proc,
+    Exec,
 }
 
 impl FromStr for Backend {
@@ -148,18 +458,349 @@ impl FromStr for Backend {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
             "kdotool" => Ok(Backend::Kdotool),
+            "kwin" => Ok(Backend::Kwin),
             "niri" => Ok(Backend::Niri),
             "xdotool" => Ok(Backend::Xdotool),
+            "x11" => Ok(Backend::X11),
+            "sway" | "i3" => Ok(Backend::Sway),
+            "hyprland" => Ok(Backend::Hyprland),
+            "wayland" => Ok(Backend::Wayland),
+            "proc" => Ok(Backend::Proc),
+            "exec" => Ok(Backend::Exec),
             _ => Err(format!("unknown backend: {}", s)),
         }
     }
 }
 
-pub fn make_lister(backend: String) -> Box<dyn WindowLister> {
-    match Backend::from_str(backend.as_str()) {
-        Ok(Backend::Kdotool) => Box::new(KdotoolLister),
-        Ok(Backend::Niri) => Box::new(NiriLister),
-        Ok(Backend::Xdotool) => Box::new(XdotoolLister),
-        Err(e) => panic!("Unknown lister `{}`: {e:?}", backend),
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Backend::Kdotool => "kdotool",
+            Backend::Kwin => "kwin",
+            Backend::Niri => "niri",
+            Backend::Xdotool => "xdotool",
+            Backend::X11 => "x11",
+            Backend::Sway => "sway",
+            Backend::Hyprland => "hyprland",
+            Backend::Wayland => "wayland",
+            Backend::Proc => "proc",
+            Backend::Exec => "exec",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Turn the configured backend name into a `Backend`; `auto` inspects the
+/// user's current session.
+pub fn resolve_backend(backend: &str, user: &str) -> Result<Backend, BackendError> {
+    if backend.eq_ignore_ascii_case("auto") {
+        return Ok(detect_backend(user));
+    }
+    Backend::from_str(backend).map_err(BackendError::Missing)
+}
+
+/// Guess the backend from the user's logind session, session environment and
+/// the compositor sockets in their runtime dir.
+pub fn detect_backend(user: &str) -> Backend {
+    let Ok(uid) = user_uid(user) else {
+        return Backend::Proc;
+    };
+    let session = graphical_session(user).unwrap_or_default();
+
+    let desktop = if session.desktop.is_empty() {
+        user_session_env(uid, "XDG_CURRENT_DESKTOP").unwrap_or_default()
+    } else {
+        session.desktop.clone()
+    }
+    .to_lowercase();
+    if desktop.contains("kde") {
+        return Backend::Kwin;
+    }
+    if desktop.contains("niri") {
+        return Backend::Niri;
+    }
+    if desktop.contains("sway") || desktop.contains("i3") {
+        return Backend::Sway;
+    }
+    if desktop.contains("hyprland") {
+        return Backend::Hyprland;
+    }
+
+    // A plain X session (e.g. Steam Big Picture) may leave no desktop name.
+    if session.kind == "x11" {
+        return Backend::X11;
+    }
+
+    let runtime_dir = format!("/run/user/{}", uid);
+    let has_socket = |dir: &str, prefix: &str| {
+        fs::read_dir(Path::new(&runtime_dir).join(dir))
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .any(|e| e.file_name().to_string_lossy().starts_with(prefix))
+            })
+            .unwrap_or(false)
+    };
+    if has_socket("", "niri.") {
+        return Backend::Niri;
+    }
+    if has_socket("", "sway-ipc.") || has_socket("i3", "ipc-socket.") {
+        return Backend::Sway;
+    }
+    if has_socket("hypr", "") {
+        return Backend::Hyprland;
+    }
+
+    if session.kind == "wayland" || user_session_env(uid, "WAYLAND_DISPLAY").is_some() {
+        return Backend::Wayland;
+    }
+    if user_session_env(uid, "DISPLAY").is_some() {
+        return Backend::X11;
+    }
+    // No graphical session at all.
+    Backend::Proc
+}
+
+/* -------------------------------------------------------------------------- */
+/* Fan-out over several backends                                              */
+/* -------------------------------------------------------------------------- */
+pub struct CompositeLister {
+    pub listers: Vec<(Backend, Box<dyn WindowLister>)>,
+}
+
+impl WindowLister for CompositeLister {
+    fn list_windows(
+        &self,
+        user: &str,
+        _backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
+        let mut result: Vec<WindowInfo> = Vec::new();
+        let mut last_error = None;
+        let mut any_ok = false;
+
+        for (backend, lister) in &self.listers {
+            // `backend_path` is specific to a single backend, so every
+            // backend falls back to its default here.
+            match lister.list_windows(user, "") {
+                Ok(windows) => {
+                    any_ok = true;
+                    for mut win in windows {
+                        win.backend = Some(backend.clone());
+                        let seen = result.iter().any(|w| same_window(w, &win));
+                        if !seen {
+                            result.push(win);
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("backend {} failed: {}", backend, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match last_error {
+            Some(e) if !any_ok => Err(e),
+            _ => Ok(result),
+        }
+    }
+
+    fn close_window(
+        &self,
+        user: &str,
+        _backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        // Window ids only mean something to the backend that reported them
+        // (a niri id is no X11 window), so only that one may close it.
+        let lister = self
+            .listers
+            .iter()
+            .find(|(backend, _)| win.backend.as_ref() == Some(backend))
+            .map(|(_, lister)| lister)
+            .ok_or_else(|| {
+                BackendError::Missing(format!("no backend reported the window of {}", win.pid))
+            })?;
+        lister.close_window(user, "", win)
+    }
+}
+
+/// Whether two windows of a backend list are the same one. A backend
+/// reports each of its windows once, and its ids tell them apart; the same
+/// window may also be seen by several backends (e.g. an XWayland window
+/// under niri and xdotool), which only agree on its pid and title.
+fn same_window(a: &WindowInfo, b: &WindowInfo) -> bool {
+    if a.backend == b.backend {
+        a.id.is_some() && a.id == b.id
+    } else {
+        a.pid == b.pid && a.title == b.title
+    }
+}
+
+fn make_single_lister(backend: &Backend, cmd_regex: Option<Regex>) -> Box<dyn WindowLister> {
+    match backend {
+        Backend::Kdotool => Box::new(KdotoolLister),
+        Backend::Kwin => Box::new(KwinLister),
+        Backend::Niri => Box::new(NiriLister),
+        Backend::Xdotool => Box::new(XdotoolLister),
+        Backend::X11 => Box::new(X11Lister),
+        Backend::Sway => Box::new(SwayLister),
+        Backend::Hyprland => Box::new(HyprlandLister),
+        Backend::Wayland => Box::new(WaylandLister),
+        Backend::Proc => Box::new(ProcLister { cmd_regex }),
+        Backend::Exec => Box::new(ExecLister),
+    }
+}
+
+/// Build the lister for the configured backends; more than one backend
+/// yields a `CompositeLister` that merges their results.
+pub fn make_lister(backends: &[Backend], cmd_regex: Option<Regex>) -> Box<dyn WindowLister> {
+    match backends {
+        [backend] => make_single_lister(backend, cmd_regex),
+        _ => Box::new(CompositeLister {
+            listers: backends
+                .iter()
+                .map(|b| (b.clone(), make_single_lister(b, cmd_regex.clone())))
+                .collect(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reports the same windows on every scan.
+    struct FixedLister(Vec<WindowInfo>);
+
+    impl WindowLister for FixedLister {
+        fn list_windows(&self, _user: &str, _path: &str) -> Result<Vec<WindowInfo>, BackendError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn window(pid: u32, title: &str, id: &str) -> WindowInfo {
+        WindowInfo {
+            title: title.to_string(),
+            pid,
+            id: Some(id.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn composite_dedup() {
+        let lister = CompositeLister {
+            listers: vec![
+                (
+                    Backend::Niri,
+                    Box::new(FixedLister(vec![
+                        window(1234, "Steam", "12"),
+                        window(1234, "Steam", "13"),
+                        window(2345, "Firefox", "14"),
+                    ])),
+                ),
+                (
+                    Backend::Xdotool,
+                    Box::new(FixedLister(vec![
+                        window(1234, "Steam", "0x3a00007"),
+                        window(3456, "Minecraft", "0x4200003"),
+                    ])),
+                ),
+            ],
+        };
+        let windows = lister.list_windows("kid", "").unwrap();
+
+        // Both Steam windows of niri stay; xdotool's copy of them does not.
+        let summary: Vec<_> = windows
+            .iter()
+            .map(|w| (w.id.as_deref().unwrap(), w.backend.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("12", Backend::Niri),
+                ("13", Backend::Niri),
+                ("14", Backend::Niri),
+                ("0x4200003", Backend::Xdotool),
+            ]
+        );
+    }
+
+    const IDS: [&str; 2] = [
+        "{0c1a4e2f-5b6d-4c3e-9f7a-1b2c3d4e5f60}",
+        "{7d8e9f01-2a3b-4c5d-8e6f-708192a3b4c5}",
+    ];
+
+    fn summary(windows: &[WindowInfo]) -> Vec<(u32, &str, Option<&str>, Option<bool>)> {
+        windows
+            .iter()
+            .map(|w| (w.pid, w.title.as_str(), w.app_id.as_deref(), w.focused))
+            .collect()
+    }
+
+    #[test]
+    fn kdotool_windows() {
+        let output = format!(
+            "1234\nsteam\nSteam\n2345\norg.kde.konsole\n~ : bash — Konsole\n{}",
+            IDS[1]
+        );
+        let windows = parse_kdotool_windows(&IDS, &output);
+        assert_eq!(
+            summary(&windows),
+            [
+                (1234, "Steam", Some("steam"), Some(false)),
+                (
+                    2345,
+                    "~ : bash — Konsole",
+                    Some("org.kde.konsole"),
+                    Some(true)
+                ),
+            ]
+        );
+        assert_eq!(windows[0].id.as_deref(), Some(IDS[0]));
+    }
+
+    #[test]
+    fn kdotool_trimmed_trailing_lines() {
+        // Empty class and title of the last window and no active window.
+        let windows = parse_kdotool_windows(&IDS, "1234\nsteam\nSteam\n2345");
+        assert_eq!(
+            summary(&windows),
+            [(1234, "Steam", Some("steam"), None), (2345, "", None, None),]
+        );
+    }
+
+    #[test]
+    fn kdotool_multi_line_titles() {
+        let output = format!(
+            "1234\nminecraft\nMinecraft\nLoading\n2345\nfirefox\nNews\n- Mozilla Firefox\n{}",
+            IDS[0]
+        );
+        let windows = parse_kdotool_windows(&IDS, &output);
+        assert_eq!(
+            summary(&windows),
+            [
+                (1234, "Minecraft\nLoading", Some("minecraft"), Some(true)),
+                (
+                    2345,
+                    "News\n- Mozilla Firefox",
+                    Some("firefox"),
+                    Some(false)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn kdotool_empty_title() {
+        let windows = parse_kdotool_windows(&IDS, "1234\nsteam\n\n2345\nmpv\nvideo.mkv");
+        assert_eq!(
+            summary(&windows),
+            [
+                (1234, "", Some("steam"), None),
+                (2345, "video.mkv", Some("mpv"), None),
+            ]
+        );
     }
 }
@@ -0,0 +1,86 @@
+use std::fmt;
+use std::io;
+
+/// Why a backend could not list the windows. `run_monitor` picks its retry
+/// and logging policy from the kind.
+#[derive(Debug)]
+pub enum BackendError {
+    /// The backend itself is unavailable: unknown backend name, tool not
+    /// installed, compositor without the needed protocol.
+    Missing(String),
+    /// The user's graphical session (or its compositor) is not running.
+    SessionNotRunning(String),
+    /// A window disappeared while it was being inspected; rescanning helps.
+    WindowVanished(String),
+    /// The backend answered with something that could not be parsed.
+    Parse(String),
+    /// Anything else.
+    Other(io::Error),
+}
+
+impl BackendError {
+    /// Classify the failure of a tool run through `run_as_user`.
+    pub fn from_command(tool: &str, err: anyhow::Error) -> Self {
+        let message = format!("{:#}", err);
+        // runuser reports a missing binary as "failed to execute <tool>" and
+        // exits with 127.
+        if message.contains("failed to execute") || message.contains("exit status: 127") {
+            return BackendError::Missing(format!("{} is not installed: {}", tool, message));
+        }
+        let no_session = [
+            "Can't open display",
+            "cannot open display",
+            "ServiceUnknown",
+            "was not provided by any .service files",
+            "error connecting to",
+            "Connection refused",
+            "No such file or directory",
+        ];
+        if no_session.iter().any(|needle| message.contains(needle)) {
+            return BackendError::SessionNotRunning(message);
+        }
+        BackendError::Other(io::Error::other(message))
+    }
+
+    /// Short name of the kind, used to tell repeated failures apart.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BackendError::Missing(_) => "missing",
+            BackendError::SessionNotRunning(_) => "session not running",
+            BackendError::WindowVanished(_) => "window vanished",
+            BackendError::Parse(_) => "parse error",
+            BackendError::Other(_) => "error",
+        }
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Missing(msg)
+            | BackendError::SessionNotRunning(msg)
+            | BackendError::WindowVanished(msg)
+            | BackendError::Parse(msg) => write!(f, "{}: {}", self.kind(), msg),
+            BackendError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+impl From<io::Error> for BackendError {
+    /// Sockets that do not exist or refuse connections mean the session is
+    /// gone; the helpers report unparsable replies as `InvalidData`.
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => {
+                BackendError::SessionNotRunning(e.to_string())
+            }
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                BackendError::Parse(e.to_string())
+            }
+            io::ErrorKind::Unsupported => BackendError::Missing(e.to_string()),
+            _ => BackendError::Other(e),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use super::{BackendError, WindowInfo, WindowLister};
+use crate::misc::run_as_user;
+use serde::Deserialize;
+
+/// Highest protocol version this build understands.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// One window as reported by the external command. Only `pid` is required;
+/// unknown fields are ignored so newer commands keep working.
+#[derive(Deserialize)]
+struct ExecWindow {
+    pid: u32,
+    #[serde(default)]
+    title: String,
+    app_id: Option<String>,
+    focused: Option<bool>,
+    id: Option<String>,
+    workspace: Option<String>,
+    fullscreen: Option<bool>,
+    hidden: Option<bool>,
+}
+
+/// Either `{"version": 1, "windows": [...]}` or, as a shorthand for
+/// version 1, the bare array.
+#[derive(Deserialize)]
+struct ExecOutput {
+    version: u32,
+    windows: Vec<ExecWindow>,
+}
+
+/* -------------------------------------------------------------------------- */
+/* Implementation running an external command (JSON on stdout)                */
+/* -------------------------------------------------------------------------- */
+pub struct ExecLister;
+
+impl WindowLister for ExecLister {
+    fn list_windows(
+        &self,
+        user: &str,
+        backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
+        // For this backend `backend_path` is the command, arguments separated
+        // by whitespace.
+        let args: Vec<&str> = backend_path.split_whitespace().collect();
+        let Some(command) = args.first() else {
+            return Err(BackendError::Missing(
+                "the exec backend needs the command in `backend_path`".to_string(),
+            ));
+        };
+
+        let output =
+            run_as_user(user, &args).map_err(|e| BackendError::from_command(command, e))?;
+
+        parse_windows(backend_path, &output)
+    }
+
+    fn close_window(
+        &self,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        // `<command> close <id>`, with the pid when the window has no id.
+        let mut args: Vec<&str> = backend_path.split_whitespace().collect();
+        let Some(command) = args.first().copied() else {
+            return Err(BackendError::Missing(
+                "the exec backend needs the command in `backend_path`".to_string(),
+            ));
+        };
+        let pid = win.pid.to_string();
+        args.push("close");
+        args.push(win.id.as_deref().unwrap_or(&pid));
+        run_as_user(user, &args).map_err(|e| BackendError::from_command(command, e))?;
+        Ok(())
+    }
+}
+
+/// Parse the windows printed by `command` in any supported protocol version.
+fn parse_windows(command: &str, output: &str) -> Result<Vec<WindowInfo>, BackendError> {
+    let invalid = |e: serde_json::Error| {
+        BackendError::Parse(format!("`{}` printed invalid window JSON: {}", command, e))
+    };
+    let windows = if output.trim_start().starts_with('[') {
+        serde_json::from_str::<Vec<ExecWindow>>(output).map_err(invalid)?
+    } else {
+        let parsed = serde_json::from_str::<ExecOutput>(output).map_err(invalid)?;
+        if !(1..=PROTOCOL_VERSION).contains(&parsed.version) {
+            return Err(BackendError::Parse(format!(
+                "`{}` speaks protocol version {}, only 1 to {} is supported",
+                command, parsed.version, PROTOCOL_VERSION
+            )));
+        }
+        parsed.windows
+    };
+
+    Ok(windows
+        .into_iter()
+        .map(|w| WindowInfo {
+            title: w.title,
+            pid: w.pid,
+            id: w.id,
+            app_id: w.app_id,
+            workspace: w.workspace,
+            fullscreen: w.fullscreen,
+            focused: w.focused,
+            hidden: w.hidden,
+            backend: None,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMAND: &str = "/usr/local/bin/list-windows --json";
+
+    #[test]
+    fn bare_array() {
+        let windows = parse_windows(
+            COMMAND,
+            r#"[{"pid": 1234, "title": "Steam", "app_id": "steam", "focused": true}]"#,
+        )
+        .unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].pid, 1234);
+        assert_eq!(windows[0].title, "Steam");
+        assert_eq!(windows[0].app_id.as_deref(), Some("steam"));
+        assert_eq!(windows[0].focused, Some(true));
+        assert_eq!(windows[0].fullscreen, None);
+    }
+
+    #[test]
+    fn versioned_object() {
+        let windows = parse_windows(
+            COMMAND,
+            r#"{"version": 1, "windows": [
+                {"pid": 1234, "id": "0x3a00007", "workspace": "2", "fullscreen": true},
+                {"pid": 2345}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].id.as_deref(), Some("0x3a00007"));
+        assert_eq!(windows[0].workspace.as_deref(), Some("2"));
+        assert_eq!(windows[0].fullscreen, Some(true));
+        assert_eq!(windows[1].pid, 2345);
+        assert_eq!(windows[1].title, "");
+    }
+
+    #[test]
+    fn unsupported_versions() {
+        for version in [0, PROTOCOL_VERSION + 1] {
+            let output = format!(r#"{{"version": {version}, "windows": []}}"#);
+            match parse_windows(COMMAND, &output) {
+                Err(BackendError::Parse(message)) => {
+                    assert!(message.contains(COMMAND), "{message}");
+                    assert!(message.contains(&format!("version {version}")), "{message}");
+                }
+                other => panic!("version {version} accepted: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn missing_pid() {
+        let result = parse_windows(COMMAND, r#"[{"title": "Steam"}]"#);
+        assert!(matches!(result, Err(BackendError::Parse(message)) if message.contains("pid")));
+    }
+
+    #[test]
+    fn unknown_fields_ignored() {
+        let windows = parse_windows(
+            COMMAND,
+            r#"{"version": 1, "monitor": "DP-1", "windows": [
+                {"pid": 1234, "title": "Steam", "opacity": 0.9, "tags": ["games"]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].pid, 1234);
+    }
+}
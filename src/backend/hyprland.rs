@@ -0,0 +1,154 @@
+use super::{BackendError, WindowInfo, WindowLister};
+use crate::misc::{user_session_env, user_uid};
+use serde::Deserialize;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+/* -------------------------------------------------------------------------- */
+/* Implementation for Hyprland (via the request socket)                       */
+/* -------------------------------------------------------------------------- */
+pub struct HyprlandLister;
+
+impl WindowLister for HyprlandLister {
+    fn list_windows(
+        &self,
+        user: &str,
+        backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
+        // For this backend `backend_path` is the `.socket.sock` path.
+        let socket = if backend_path.is_empty() {
+            find_socket(user)?
+        } else {
+            PathBuf::from(backend_path)
+        };
+
+        // Same request as `hyprctl clients -j`.
+        let reply = hypr_request(&socket, "j/clients")?;
+
+        #[derive(Deserialize)]
+        struct HyprWorkspace {
+            name: String,
+        }
+
+        #[derive(Deserialize)]
+        struct HyprClient {
+            address: String,
+            pid: i64,
+            title: String,
+            class: String,
+            workspace: HyprWorkspace,
+            /// A bool in older releases, a fullscreen mode (0 = none) in newer.
+            fullscreen: serde_json::Value,
+            /// 0 for the focused window.
+            #[serde(rename = "focusHistoryID")]
+            focus_history_id: i64,
+            /// Set for windows not shown, e.g. inactive members of a group.
+            #[serde(default)]
+            hidden: bool,
+        }
+
+        let parsed: Vec<HyprClient> = serde_json::from_slice(&reply).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("failed to parse Hyprland JSON: {}", e),
+            )
+        })?;
+
+        Ok(parsed
+            .into_iter()
+            .filter_map(|c| {
+                let fullscreen = match c.fullscreen {
+                    serde_json::Value::Bool(b) => Some(b),
+                    serde_json::Value::Number(n) => n.as_i64().map(|mode| mode != 0),
+                    _ => None,
+                };
+                Some(WindowInfo {
+                    pid: u32::try_from(c.pid).ok()?,
+                    title: c.title,
+                    id: Some(c.address),
+                    app_id: Some(c.class).filter(|class| !class.is_empty()),
+                    workspace: Some(c.workspace.name),
+                    fullscreen,
+                    focused: Some(c.focus_history_id == 0),
+                    hidden: Some(c.hidden),
+                    backend: None,
+                })
+            })
+            .collect())
+    }
+
+    fn close_window(
+        &self,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        let address = win
+            .id
+            .as_deref()
+            .ok_or_else(|| BackendError::Missing(format!("no window address for {}", win.pid)))?;
+        let socket = if backend_path.is_empty() {
+            find_socket(user)?
+        } else {
+            PathBuf::from(backend_path)
+        };
+
+        // Same as `hyprctl dispatch closewindow address:<address>`.
+        let reply = hypr_request(
+            &socket,
+            &format!("dispatch closewindow address:{}", address),
+        )?;
+        let reply = String::from_utf8_lossy(&reply);
+        if reply.trim() == "ok" {
+            Ok(())
+        } else {
+            Err(BackendError::WindowVanished(format!(
+                "Hyprland refused to close {}: {}",
+                address,
+                reply.trim()
+            )))
+        }
+    }
+}
+
+/// Locate `$XDG_RUNTIME_DIR/hypr/<signature>/.socket.sock` of the user's
+/// Hyprland instance.
+fn find_socket(user: &str) -> io::Result<PathBuf> {
+    let uid = user_uid(user).map_err(io::Error::other)?;
+    // Same runtime dir `run_as_user` hands to the user's commands.
+    let hypr_dir = PathBuf::from(format!("/run/user/{}/hypr", uid));
+
+    if let Some(signature) = user_session_env(uid, "HYPRLAND_INSTANCE_SIGNATURE") {
+        let socket = hypr_dir.join(signature).join(".socket.sock");
+        if socket.exists() {
+            return Ok(socket);
+        }
+    }
+
+    // No running process exported the signature; pick the most recently
+    // started instance that still has a socket.
+    let newest = fs::read_dir(&hypr_dir)?
+        .flatten()
+        .map(|entry| entry.path().join(".socket.sock"))
+        .filter(|socket| socket.exists())
+        .max_by_key(|socket| socket.metadata().and_then(|m| m.modified()).ok());
+
+    newest.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no Hyprland instance found for {}", user),
+        )
+    })
+}
+
+/// Send one request to Hyprland's socket and read the reply until EOF.
+fn hypr_request(socket: &Path, request: &str) -> io::Result<Vec<u8>> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.write_all(request.as_bytes())?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+    Ok(reply)
+}
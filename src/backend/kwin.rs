@@ -0,0 +1,255 @@
+use super::{BackendError, WindowInfo, WindowLister};
+use crate::misc::run_as_user;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::io;
+use std::sync::mpsc;
+use std::time::Duration;
+use zbus::blocking::{Connection, connection};
+
+/// Object path and interface the KWin script reports back to.
+const REPORT_PATH: &str = "/parental_watchdog";
+const REPORT_INTERFACE: &str = "org.parental_watchdog.KWinReport";
+
+/// How long to wait for the script to call back.
+const REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs inside KWin. Written in ES5 so that the QtScript engine of Plasma 5
+/// can run it too; Plasma 6 renamed `clientList`/`activeClient`.
+const SCRIPT: &str = r#"
+var clients = workspace.windowList ? workspace.windowList() : workspace.clientList();
+var active = workspace.windowList ? workspace.activeWindow : workspace.activeClient;
+var windows = [];
+for (var i = 0; i < clients.length; i++) {
+    var w = clients[i];
+    if (!w.normalWindow) {
+        continue;
+    }
+    var desktop = w.desktops
+        ? (w.desktops.length ? w.desktops[0].name : "")
+        : String(w.desktop);
+    windows.push({
+        "id": String(w.internalId),
+        "pid": w.pid,
+        "title": w.caption,
+        "class": String(w.resourceClass),
+        "desktop": desktop,
+        "fullscreen": w.fullScreen,
+        "minimized": w.minimized,
+        "focused": w === active
+    });
+}
+callDBus("%SERVICE%", "%PATH%", "%INTERFACE%", "Report", JSON.stringify(windows));
+"#;
+
+/// Asks the window with the internal id `%ID%` to close, as its close button
+/// would, and reports whether it was found.
+const CLOSE_SCRIPT: &str = r#"
+var clients = workspace.windowList ? workspace.windowList() : workspace.clientList();
+var closed = false;
+for (var i = 0; i < clients.length; i++) {
+    if (String(clients[i].internalId) === %ID%) {
+        clients[i].closeWindow();
+        closed = true;
+    }
+}
+callDBus("%SERVICE%", "%PATH%", "%INTERFACE%", "Report", closed ? "closed" : "");
+"#;
+
+/* -------------------------------------------------------------------------- */
+/* Implementation for KDE (KWin scripting over D-Bus, no kdotool needed)      */
+/* -------------------------------------------------------------------------- */
+pub struct KwinLister;
+
+impl WindowLister for KwinLister {
+    fn list_windows(
+        &self,
+        user: &str,
+        backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
+        // The session bus only accepts the user's own connections, so the
+        // D-Bus part runs in a copy of this binary started as the user
+        // (`parental-watchdog kwin-windows`). `backend_path` overrides the
+        // binary.
+        let exec_path = helper_path(backend_path)?;
+        let output = run_as_user(user, &[&exec_path, "kwin-windows"])
+            .map_err(|e| BackendError::from_command("kwin-windows", e))?;
+
+        #[derive(Deserialize)]
+        struct KwinWindow {
+            id: String,
+            pid: i64,
+            title: String,
+            class: String,
+            desktop: String,
+            fullscreen: bool,
+            minimized: bool,
+            focused: bool,
+        }
+
+        let parsed: Vec<KwinWindow> = serde_json::from_str(&output).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("failed to parse KWin JSON: {}", e),
+            )
+        })?;
+
+        Ok(parsed
+            .into_iter()
+            .filter_map(|w| {
+                Some(WindowInfo {
+                    // Internal windows (e.g. KWin's own) have no client pid.
+                    pid: u32::try_from(w.pid).ok().filter(|&pid| pid > 0)?,
+                    title: w.title,
+                    id: Some(w.id),
+                    app_id: Some(w.class).filter(|class| !class.is_empty()),
+                    workspace: Some(w.desktop).filter(|desktop| !desktop.is_empty()),
+                    fullscreen: Some(w.fullscreen),
+                    focused: Some(w.focused),
+                    hidden: Some(w.minimized),
+                    backend: None,
+                })
+            })
+            .collect())
+    }
+
+    fn close_window(
+        &self,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        let id = super::window_id(win)?;
+        let exec_path = helper_path(backend_path)?;
+        run_as_user(user, &[&exec_path, "kwin-close", id])
+            .map_err(|e| BackendError::from_command("kwin-close", e))?;
+        Ok(())
+    }
+}
+
+/// The binary that runs the D-Bus part as the user: this one, unless
+/// `backend_path` names another.
+fn helper_path(backend_path: &str) -> io::Result<String> {
+    if backend_path.is_empty() {
+        Ok(env::current_exe()?.to_string_lossy().into_owned())
+    } else {
+        Ok(backend_path.to_string())
+    }
+}
+
+/// Receives the report of the KWin script.
+struct Reporter {
+    tx: mpsc::Sender<String>,
+}
+
+#[zbus::interface(name = "org.parental_watchdog.KWinReport")]
+impl Reporter {
+    fn report(&self, windows: String) {
+        let _ = self.tx.send(windows);
+    }
+}
+
+/// Load the reporting script into the KWin of the current session bus, run
+/// it once and return the JSON it reported. Must run as the session's user.
+pub fn query_kwin_windows() -> io::Result<String> {
+    run_reporting_script(SCRIPT)
+}
+
+/// Ask KWin of the current session bus to close the window with the given
+/// internal id. Must run as the session's user.
+pub fn close_kwin_window(id: &str) -> io::Result<()> {
+    // A JSON string is a valid JavaScript string literal.
+    let literal = serde_json::to_string(id).map_err(io::Error::other)?;
+    match run_reporting_script(&CLOSE_SCRIPT.replace("%ID%", &literal))?.as_str() {
+        "closed" => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("KWin has no window {}", id),
+        )),
+    }
+}
+
+/// Run `script` once in KWin and return what it passed to `Report`.
+fn run_reporting_script(script: &str) -> io::Result<String> {
+    let (tx, rx) = mpsc::channel();
+    let conn = connection::Builder::session()
+        .and_then(|b| b.serve_at(REPORT_PATH, Reporter { tx }))
+        .and_then(|b| b.build())
+        .map_err(io::Error::other)?;
+    let service = conn
+        .unique_name()
+        .map(|name| name.to_string())
+        .ok_or_else(|| io::Error::other("no unique name on the session bus"))?;
+
+    let plugin = format!("parental-watchdog-{}", std::process::id());
+    let dir = env::var_os("XDG_RUNTIME_DIR")
+        .map(Into::into)
+        .unwrap_or_else(env::temp_dir);
+    let script_path = dir.join(format!("{plugin}.js"));
+    fs::write(
+        &script_path,
+        script
+            .replace("%SERVICE%", &service)
+            .replace("%PATH%", REPORT_PATH)
+            .replace("%INTERFACE%", REPORT_INTERFACE),
+    )?;
+
+    let result = run_script(&conn, &script_path.to_string_lossy(), &plugin)
+        .and_then(|()| rx.recv_timeout(REPORT_TIMEOUT).map_err(io::Error::other));
+
+    let _ = scripting_call::<bool>(&conn, "unloadScript", &(plugin.as_str(),));
+    let _ = fs::remove_file(&script_path);
+    result
+}
+
+fn run_script(conn: &Connection, path: &str, plugin: &str) -> io::Result<()> {
+    let id: i32 = scripting_call(conn, "loadScript", &(path, plugin))?;
+    if id < 0 {
+        return Err(io::Error::other(format!(
+            "KWin refused to load the script {}",
+            path
+        )));
+    }
+
+    // Plasma 6 exports loaded scripts below `/Scripting`, Plasma 5 at the root.
+    let mut last_error = None;
+    for script in [format!("/Scripting/Script{id}"), format!("/{id}")] {
+        match conn.call_method(
+            Some("org.kde.KWin"),
+            script.as_str(),
+            Some("org.kde.kwin.Script"),
+            "run",
+            &(),
+        ) {
+            Ok(_) => return Ok(()),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(io::Error::other(format!(
+        "failed to run KWin script {}: {}",
+        id,
+        last_error.map(|e| e.to_string()).unwrap_or_default()
+    )))
+}
+
+/// Call a method of KWin's `org.kde.kwin.Scripting` interface.
+fn scripting_call<R>(
+    conn: &Connection,
+    method: &str,
+    args: &(impl serde::Serialize + zbus::zvariant::DynamicType),
+) -> io::Result<R>
+where
+    R: for<'d> zbus::zvariant::DynamicDeserialize<'d>,
+{
+    let reply = conn
+        .call_method(
+            Some("org.kde.KWin"),
+            "/Scripting",
+            Some("org.kde.kwin.Scripting"),
+            method,
+            args,
+        )
+        .map_err(io::Error::other)?;
+    reply.body().deserialize().map_err(io::Error::other)
+}
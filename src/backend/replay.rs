@@ -0,0 +1,178 @@
+use super::{BackendError, WindowInfo, WindowLister};
+use crate::process::{ProcessHandle, ProcessInfo, ProcessInspector};
+use chrono::{DateTime, Local, NaiveDateTime};
+use serde::Deserialize;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// One window of a snapshot, with the process details `ps` would report.
+#[derive(Deserialize)]
+struct ReplayWindow {
+    pid: u32,
+    #[serde(default)]
+    title: String,
+    app_id: Option<String>,
+    /// Full command line.
+    #[serde(default)]
+    cmdline: String,
+    /// Process name; defaults to the basename of the first word of `cmdline`.
+    comm: Option<String>,
+    /// Seconds the process has been running; defaults to the time since the
+    /// pid first appeared in the timeline.
+    elapsed: Option<i64>,
+    focused: Option<bool>,
+    fullscreen: Option<bool>,
+    hidden: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    /// Local time of the scan, e.g. `2026-10-17 14:30:00`.
+    at: String,
+    #[serde(default)]
+    windows: Vec<ReplayWindow>,
+}
+
+/* -------------------------------------------------------------------------- */
+/* Implementation replaying a recorded timeline (no window system)            */
+/* -------------------------------------------------------------------------- */
+/// Plays back a timeline of window snapshots. It is also the process
+/// inspector for the replayed pids, so nothing on the machine is looked at.
+pub struct ReplayLister {
+    snapshots: Vec<(DateTime<Local>, Snapshot)>,
+    /// When each pid first shows up in the timeline.
+    first_seen: HashMap<u32, DateTime<Local>>,
+    current: Cell<usize>,
+}
+
+impl ReplayLister {
+    /// Read a YAML (or JSON) list of snapshots, sorted by time.
+    pub fn load(path: &Path) -> Result<Self, BackendError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| BackendError::Missing(format!("{}: {}", path.display(), e)))?;
+        let parsed: Vec<Snapshot> = serde_yaml::from_str(&content)
+            .map_err(|e| BackendError::Parse(format!("failed to parse timeline: {}", e)))?;
+
+        let mut snapshots = Vec::with_capacity(parsed.len());
+        for snapshot in parsed {
+            let at = parse_time(&snapshot.at).ok_or_else(|| {
+                BackendError::Parse(format!("invalid snapshot time `{}`", snapshot.at))
+            })?;
+            snapshots.push((at, snapshot));
+        }
+        snapshots.sort_by_key(|(at, _)| *at);
+
+        let mut first_seen = HashMap::new();
+        for (at, snapshot) in &snapshots {
+            for win in &snapshot.windows {
+                first_seen.entry(win.pid).or_insert(*at);
+            }
+        }
+
+        Ok(ReplayLister {
+            snapshots,
+            first_seen,
+            current: Cell::new(0),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Make snapshot `index` the current one and return its time.
+    pub fn seek(&self, index: usize) -> DateTime<Local> {
+        self.current.set(index);
+        self.snapshots[index].0
+    }
+
+    /// Time of the current snapshot.
+    pub fn now(&self) -> DateTime<Local> {
+        self.current().0
+    }
+
+    fn current(&self) -> &(DateTime<Local>, Snapshot) {
+        &self.snapshots[self.current.get()]
+    }
+}
+
+impl WindowLister for ReplayLister {
+    fn list_windows(
+        &self,
+        _user: &str,
+        _backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
+        let (_, snapshot) = self.current();
+        Ok(snapshot
+            .windows
+            .iter()
+            .map(|w| WindowInfo {
+                title: w.title.clone(),
+                pid: w.pid,
+                app_id: w.app_id.clone(),
+                fullscreen: w.fullscreen,
+                focused: w.focused,
+                hidden: w.hidden,
+                backend: None,
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    /// Windows of a replay close on request; whether they are gone is up to
+    /// the next snapshots.
+    fn close_window(
+        &self,
+        _user: &str,
+        _backend_path: &str,
+        _win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        Ok(())
+    }
+}
+
+impl ProcessInspector for ReplayLister {
+    fn inspect(&self, pid: u32) -> io::Result<ProcessInfo> {
+        let (at, snapshot) = self.current();
+        let win = snapshot
+            .windows
+            .iter()
+            .find(|w| w.pid == pid)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("pid {pid} not replayed"))
+            })?;
+
+        let etimes = win.elapsed.unwrap_or_else(|| {
+            self.first_seen
+                .get(&pid)
+                .map(|first| (*at - *first).num_seconds())
+                .unwrap_or(0)
+        });
+        let comm = win.comm.clone().unwrap_or_else(|| {
+            let program = win.cmdline.split_whitespace().next().unwrap_or_default();
+            program.rsplit('/').next().unwrap_or_default().to_string()
+        });
+
+        Ok(ProcessInfo {
+            etimes,
+            comm,
+            command: win.cmdline.clone(),
+            handle: ProcessHandle::detached(pid),
+        })
+    }
+}
+
+fn parse_time(at: &str) -> Option<DateTime<Local>> {
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(at, format).ok())?
+        .and_local_timezone(Local)
+        .earliest()
+}
@@ -0,0 +1,323 @@
+use super::{BackendError, WindowInfo, WindowLister, x11};
+use crate::misc::{user_session_env, user_uid};
+use serde::Deserialize;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+const IPC_MAGIC: &[u8] = b"i3-ipc";
+const IPC_RUN_COMMAND: u32 = 0;
+const IPC_GET_TREE: u32 = 4;
+
+/* -------------------------------------------------------------------------- */
+/* Implementation for sway / i3 (via the IPC socket)                          */
+/* -------------------------------------------------------------------------- */
+pub struct SwayLister;
+
+impl WindowLister for SwayLister {
+    fn list_windows(
+        &self,
+        user: &str,
+        backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
+        // For this backend `backend_path` is the IPC socket path.
+        let socket = if backend_path.is_empty() {
+            find_socket(user)?
+        } else {
+            PathBuf::from(backend_path)
+        };
+
+        let reply = ipc_request(&socket, IPC_GET_TREE, b"")?;
+        let tree: SwayNode = serde_json::from_slice(&reply).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("failed to parse sway/i3 tree JSON: {}", e),
+            )
+        })?;
+
+        let mut windows = Vec::new();
+        collect_windows(&tree, None, &mut windows);
+
+        // i3 does not report pids, only X11 window ids; ask the X server.
+        let missing: Vec<u32> = windows
+            .iter()
+            .filter(|(w, _)| w.pid.is_none())
+            .filter_map(|(w, _)| w.window)
+            .collect();
+        let x11_pids = if missing.is_empty() {
+            Default::default()
+        } else {
+            x11::window_pids(user, "", &missing).unwrap_or_else(|e| {
+                eprintln!("failed to resolve i3 window pids via X11: {}", e);
+                Default::default()
+            })
+        };
+
+        Ok(windows
+            .into_iter()
+            .filter_map(|(w, workspace)| {
+                let pid = w
+                    .pid
+                    .or_else(|| w.window.and_then(|id| x11_pids.get(&id).copied()))?;
+                let class = w.window_properties.as_ref().and_then(|p| p.class.clone());
+                Some(WindowInfo {
+                    title: w.name.clone().unwrap_or_default(),
+                    pid,
+                    id: Some(w.id.to_string()),
+                    app_id: w.app_id.clone().or(class),
+                    workspace: workspace.map(str::to_string),
+                    fullscreen: Some(w.fullscreen_mode > 0),
+                    focused: Some(w.focused),
+                    hidden: w.visible.map(|visible| !visible),
+                    backend: None,
+                })
+            })
+            .collect())
+    }
+
+    fn close_window(
+        &self,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        let id = win.id.as_deref().ok_or_else(|| {
+            BackendError::Missing(format!("no container id for the window of {}", win.pid))
+        })?;
+        let socket = if backend_path.is_empty() {
+            find_socket(user)?
+        } else {
+            PathBuf::from(backend_path)
+        };
+
+        // `kill` closes the container gracefully (WM_DELETE_WINDOW / xdg close).
+        let command = format!("[con_id={}] kill", id);
+        let reply = ipc_request(&socket, IPC_RUN_COMMAND, command.as_bytes())?;
+
+        #[derive(Deserialize)]
+        struct CommandResult {
+            success: bool,
+            error: Option<String>,
+        }
+        let results: Vec<CommandResult> = serde_json::from_slice(&reply).map_err(|e| {
+            BackendError::Parse(format!("failed to parse sway/i3 command reply: {}", e))
+        })?;
+        match results.into_iter().find(|r| !r.success) {
+            Some(failed) => Err(BackendError::WindowVanished(
+                failed
+                    .error
+                    .unwrap_or_else(|| format!("failed to close {}", id)),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SwayNode {
+    id: i64,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    pid: Option<u32>,
+    #[serde(default)]
+    name: Option<String>,
+    /// Native Wayland windows carry an `app_id`, X11 ones a window id and
+    /// `window_properties`.
+    #[serde(default)]
+    app_id: Option<String>,
+    #[serde(default)]
+    window: Option<u32>,
+    #[serde(default)]
+    window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    focused: bool,
+    #[serde(default)]
+    fullscreen_mode: u8,
+    /// Only present on views; false when on a hidden workspace.
+    #[serde(default)]
+    visible: Option<bool>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+#[derive(Deserialize)]
+struct WindowProperties {
+    #[serde(default)]
+    class: Option<String>,
+}
+
+/// Walk the layout tree and collect the leaves that are real windows,
+/// together with the name of the workspace they live on. i3's dock areas
+/// only hold bars, not windows.
+fn collect_windows<'a>(
+    node: &'a SwayNode,
+    workspace: Option<&'a str>,
+    out: &mut Vec<(&'a SwayNode, Option<&'a str>)>,
+) {
+    if node.kind == "dockarea" {
+        return;
+    }
+    let workspace = if node.kind == "workspace" {
+        node.name.as_deref()
+    } else {
+        workspace
+    };
+    if node.app_id.is_some() || node.window.is_some() {
+        out.push((node, workspace));
+    }
+    for child in node.nodes.iter().chain(&node.floating_nodes) {
+        collect_windows(child, workspace, out);
+    }
+}
+
+/// Locate the IPC socket of the user's sway or i3 instance.
+fn find_socket(user: &str) -> io::Result<PathBuf> {
+    let uid = user_uid(user).map_err(io::Error::other)?;
+    if let Some(path) =
+        user_session_env(uid, "SWAYSOCK").or_else(|| user_session_env(uid, "I3SOCK"))
+    {
+        return Ok(PathBuf::from(path));
+    }
+
+    // Fall back to the default locations: `sway-ipc.<uid>.<pid>.sock` and
+    // `i3/ipc-socket.<pid>` in the runtime dir.
+    let runtime_dir = PathBuf::from(format!("/run/user/{}", uid));
+    let candidates = [
+        (runtime_dir.clone(), "sway-ipc."),
+        (runtime_dir.join("i3"), "ipc-socket."),
+    ];
+    for (dir, prefix) in candidates {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with(prefix) {
+                return Ok(entry.path());
+            }
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no sway/i3 IPC socket found for {}", user),
+    ))
+}
+
+/// Send one i3-ipc message and return the payload of the reply.
+fn ipc_request(socket: &Path, msg_type: u32, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let mut stream = UnixStream::connect(socket)?;
+
+    let mut msg = Vec::with_capacity(IPC_MAGIC.len() + 8 + payload.len());
+    msg.extend_from_slice(IPC_MAGIC);
+    msg.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    msg.extend_from_slice(&msg_type.to_ne_bytes());
+    msg.extend_from_slice(payload);
+    stream.write_all(&msg)?;
+
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != IPC_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid i3-ipc reply header",
+        ));
+    }
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+    let mut reply = vec![0u8; len];
+    stream.read_exact(&mut reply)?;
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows(tree: &SwayNode) -> Vec<(&SwayNode, Option<&str>)> {
+        let mut windows = Vec::new();
+        collect_windows(tree, None, &mut windows);
+        windows
+    }
+
+    #[test]
+    fn sway_tree() {
+        let tree: SwayNode =
+            serde_json::from_str(include_str!("../../tests/fixtures/sway_get_tree.json")).unwrap();
+        let windows = windows(&tree);
+
+        let summary: Vec<_> = windows
+            .iter()
+            .map(|(w, workspace)| (w.id, w.pid, *workspace))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (5, Some(1234), Some("1: web")),
+                (8, Some(2345), Some("2")),
+                (9, Some(4567), Some("2")),
+                (10, Some(3456), Some("2")),
+            ]
+        );
+
+        let [(firefox, _), (steam, _), (mpv, _), (pavucontrol, _)] = windows[..] else {
+            unreachable!()
+        };
+        assert_eq!(firefox.app_id.as_deref(), Some("org.mozilla.firefox"));
+        assert!(firefox.focused);
+        assert_eq!(firefox.visible, Some(true));
+        assert_eq!(firefox.fullscreen_mode, 0);
+
+        // XWayland view: no app_id, but a pid and the X11 class.
+        assert_eq!(steam.app_id, None);
+        assert_eq!(steam.window, Some(4194307));
+        let class = steam.window_properties.as_ref().unwrap().class.as_deref();
+        assert_eq!(class, Some("steam"));
+        assert_eq!(steam.visible, Some(false));
+
+        // Fullscreen in a background tab.
+        assert_eq!(mpv.fullscreen_mode, 1);
+        assert_eq!(mpv.visible, Some(false));
+
+        assert_eq!(pavucontrol.kind, "floating_con");
+        assert_eq!(pavucontrol.name.as_deref(), Some("Volume Control"));
+    }
+
+    #[test]
+    fn i3_tree() {
+        let tree: SwayNode =
+            serde_json::from_str(include_str!("../../tests/fixtures/i3_get_tree.json")).unwrap();
+        let windows = windows(&tree);
+
+        // The scratchpad window is found, the i3bar dock client is not.
+        let summary: Vec<_> = windows
+            .iter()
+            .map(|(w, workspace)| (w.window, *workspace))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Some(18874374), Some("__i3_scratch")),
+                (Some(23068679), Some("1")),
+            ]
+        );
+
+        // i3 reports neither pids nor visibility; pids come from X11.
+        for (w, _) in &windows {
+            assert_eq!(w.pid, None);
+            assert_eq!(w.app_id, None);
+            assert_eq!(w.visible, None);
+        }
+        let (minecraft, _) = windows[1];
+        assert!(minecraft.focused);
+        assert_eq!(minecraft.fullscreen_mode, 1);
+        let class = minecraft
+            .window_properties
+            .as_ref()
+            .unwrap()
+            .class
+            .as_deref();
+        assert_eq!(class, Some("Minecraft 1.20.4"));
+    }
+}
@@ -0,0 +1,354 @@
+use super::{BackendError, WindowInfo, WindowLister};
+use crate::misc::{process_name, user_pids, user_session_env, user_uid};
+use crate::process::descendants;
+use std::fs;
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{GlobalListContents, registry_queue_init};
+use wayland_client::protocol::wl_registry;
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, event_created_child};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+/* -------------------------------------------------------------------------- */
+/* Implementation for wlroots-style compositors (foreign toplevel protocols)  */
+/* -------------------------------------------------------------------------- */
+pub struct WaylandLister;
+
+impl WindowLister for WaylandLister {
+    fn list_windows(
+        &self,
+        user: &str,
+        backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
+        let uid = user_uid(user).map_err(|e| BackendError::Other(io::Error::other(e)))?;
+
+        // For this backend `backend_path` is the Wayland socket (name or path).
+        let display = if backend_path.is_empty() {
+            user_session_env(uid, "WAYLAND_DISPLAY").unwrap_or_else(|| "wayland-0".to_string())
+        } else {
+            backend_path.to_string()
+        };
+        let socket = PathBuf::from(format!("/run/user/{}", uid)).join(display);
+
+        let toplevels = list_toplevels(UnixStream::connect(&socket)?)?;
+
+        // Neither protocol tells us which client owns a toplevel, so the pid
+        // is resolved from the app_id against the user's processes. Windows
+        // it cannot be pinned to are not enforced at all.
+        let mut result = Vec::new();
+        for toplevel in toplevels {
+            match resolve_app_pid(uid, &toplevel.app_id) {
+                Some(pid) => result.push(WindowInfo {
+                    title: toplevel.title,
+                    pid,
+                    id: toplevel.identifier,
+                    app_id: Some(toplevel.app_id),
+                    workspace: None,
+                    fullscreen: toplevel.fullscreen,
+                    focused: toplevel.focused,
+                    hidden: toplevel.minimized,
+                    backend: None,
+                }),
+                None => eprintln!(
+                    "no single process found for Wayland app_id '{}' (title '{}'), ignoring it",
+                    toplevel.app_id, toplevel.title
+                ),
+            }
+        }
+        Ok(result)
+    }
+
+    fn close_window(
+        &self,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        let uid = user_uid(user).map_err(|e| BackendError::Other(io::Error::other(e)))?;
+        let display = if backend_path.is_empty() {
+            user_session_env(uid, "WAYLAND_DISPLAY").unwrap_or_else(|| "wayland-0".to_string())
+        } else {
+            backend_path.to_string()
+        };
+        let socket = PathBuf::from(format!("/run/user/{}", uid)).join(display);
+
+        let app_id = win.app_id.as_deref().unwrap_or_default();
+        Ok(close_toplevel(
+            UnixStream::connect(&socket)?,
+            app_id,
+            &win.title,
+        )?)
+    }
+}
+
+struct Toplevel {
+    id: ObjectId,
+    title: String,
+    app_id: String,
+    /// Stable identifier (ext protocol only).
+    identifier: Option<String>,
+    /// Activation/fullscreen/minimised state (wlr protocol only).
+    focused: Option<bool>,
+    fullscreen: Option<bool>,
+    minimized: Option<bool>,
+    closed: bool,
+    /// Handle to close the toplevel with (wlr protocol only).
+    wlr: Option<ZwlrForeignToplevelHandleV1>,
+}
+
+#[derive(Default)]
+struct State {
+    toplevels: Vec<Toplevel>,
+}
+
+impl State {
+    fn toplevel(&mut self, id: ObjectId) -> &mut Toplevel {
+        let pos = match self.toplevels.iter().position(|t| t.id == id) {
+            Some(pos) => pos,
+            None => {
+                self.toplevels.push(Toplevel {
+                    id,
+                    title: String::new(),
+                    app_id: String::new(),
+                    identifier: None,
+                    focused: None,
+                    fullscreen: None,
+                    minimized: None,
+                    closed: false,
+                    wlr: None,
+                });
+                self.toplevels.len() - 1
+            }
+        };
+        &mut self.toplevels[pos]
+    }
+}
+
+/// Bind `zwlr_foreign_toplevel_manager_v1`, which also reports the focus,
+/// fullscreen and minimised state (or, failing that,
+/// `ext_foreign_toplevel_list_v1`) and collect the initial toplevel state.
+fn list_toplevels(stream: UnixStream) -> io::Result<Vec<Toplevel>> {
+    let conn = Connection::from_socket(stream).map_err(io::Error::other)?;
+    let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(io::Error::other)?;
+    let qh = queue.handle();
+
+    let wlr = globals.bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ());
+    let ext = match wlr {
+        Ok(_) => None,
+        Err(_) => Some(
+            globals
+                .bind::<ExtForeignToplevelListV1, _, _>(&qh, 1..=1, ())
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        format!("compositor offers no foreign toplevel protocol: {}", e),
+                    )
+                })?,
+        ),
+    };
+
+    let mut state = State::default();
+    // First roundtrip announces the toplevels, the second one flushes their
+    // initial title/app_id events.
+    queue.roundtrip(&mut state).map_err(io::Error::other)?;
+    queue.roundtrip(&mut state).map_err(io::Error::other)?;
+
+    if let Ok(manager) = wlr {
+        manager.stop();
+    }
+    if let Some(list) = ext {
+        list.stop();
+    }
+    let _ = conn.flush();
+
+    Ok(state.toplevels.into_iter().filter(|t| !t.closed).collect())
+}
+
+/// Close the first toplevel with the given app_id and title. Only the wlr
+/// protocol can close windows; the ext list is read-only.
+fn close_toplevel(stream: UnixStream, app_id: &str, title: &str) -> io::Result<()> {
+    let conn = Connection::from_socket(stream).map_err(io::Error::other)?;
+    let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(io::Error::other)?;
+    let qh = queue.handle();
+    let manager = globals
+        .bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ())
+        .map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "compositor cannot close windows (no wlr foreign toplevel): {}",
+                    e
+                ),
+            )
+        })?;
+
+    let mut state = State::default();
+    queue.roundtrip(&mut state).map_err(io::Error::other)?;
+    queue.roundtrip(&mut state).map_err(io::Error::other)?;
+
+    let handle = state
+        .toplevels
+        .iter()
+        .filter(|t| !t.closed && t.app_id == app_id && t.title == title)
+        .find_map(|t| t.wlr.clone())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no toplevel '{}' ({}) to close", title, app_id),
+            )
+        })?;
+    handle.close();
+    manager.stop();
+    queue.roundtrip(&mut state).map_err(io::Error::other)?;
+    Ok(())
+}
+
+/// The process of `uid` owning the windows with `app_id` (e.g.
+/// `org.mozilla.firefox`): the process whose name or executable matches it,
+/// if all other matching processes are its descendants. `None` when nothing
+/// matches or several instances run, as there is no telling which one owns
+/// the window.
+fn resolve_app_pid(uid: u32, app_id: &str) -> Option<u32> {
+    if app_id.is_empty() {
+        return None;
+    }
+    let app_id = app_id.to_lowercase();
+    let short = app_id.rsplit('.').next().unwrap_or(&app_id);
+
+    let candidates: Vec<u32> = user_pids(uid)
+        .into_iter()
+        .filter(|pid| {
+            let comm = process_name(*pid).unwrap_or_default().to_lowercase();
+            let exe = fs::read_link(format!("/proc/{pid}/exe"))
+                .ok()
+                .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_lowercase()))
+                .unwrap_or_default();
+            [comm.as_str(), exe.as_str()]
+                .iter()
+                .any(|name| !name.is_empty() && (*name == app_id || *name == short))
+        })
+        .collect();
+
+    let mut roots = candidates.iter().filter(|&&pid| {
+        let children = descendants(pid);
+        candidates
+            .iter()
+            .all(|other| *other == pid || children.contains(other))
+    });
+    match (roots.next(), roots.next()) {
+        (Some(&pid), None) => Some(pid),
+        _ => None,
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event {
+            state.toplevel(toplevel.id());
+        }
+    }
+
+    event_created_child!(State, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let toplevel = state.toplevel(handle.id());
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = title,
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => toplevel.app_id = app_id,
+            ext_foreign_toplevel_handle_v1::Event::Identifier { identifier } => {
+                toplevel.identifier = Some(identifier)
+            }
+            ext_foreign_toplevel_handle_v1::Event::Closed => toplevel.closed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            state.toplevel(toplevel.id());
+        }
+    }
+
+    event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let toplevel = state.toplevel(handle.id());
+        toplevel.wlr.get_or_insert_with(|| handle.clone());
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = title,
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => toplevel.app_id = app_id,
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                // An array of native endian u32 `state` enum values.
+                let states: Vec<u32> = state
+                    .chunks_exact(4)
+                    .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                let has = |s: zwlr_foreign_toplevel_handle_v1::State| states.contains(&(s as u32));
+                toplevel.focused = Some(has(zwlr_foreign_toplevel_handle_v1::State::Activated));
+                toplevel.fullscreen = Some(has(zwlr_foreign_toplevel_handle_v1::State::Fullscreen));
+                toplevel.minimized = Some(has(zwlr_foreign_toplevel_handle_v1::State::Minimized));
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => toplevel.closed = true,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use super::{BackendError, WindowInfo, WindowLister};
+use crate::misc::{user_session_env, user_uid};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use users::get_user_by_name;
+use users::os::unix::UserExt;
+use x11rb::connection::Connection;
+use x11rb::protocol::screensaver::ConnectionExt as _;
+use x11rb::protocol::xproto::{AtomEnum, ClientMessageEvent, ConnectionExt, EventMask, Window};
+use x11rb::reexports::x11rb_protocol::parse_display::parse_display;
+use x11rb::reexports::x11rb_protocol::xauth::Family;
+use x11rb::rust_connection::{DefaultStream, RustConnection};
+
+const MIT_MAGIC_COOKIE_1: &[u8] = b"MIT-MAGIC-COOKIE-1";
+
+/* -------------------------------------------------------------------------- */
+/* Implementation for X11 (talks to the X server directly)                    */
+/* -------------------------------------------------------------------------- */
+pub struct X11Lister;
+
+impl WindowLister for X11Lister {
+    fn list_windows(
+        &self,
+        user: &str,
+        backend_path: &str,
+    ) -> Result<Vec<WindowInfo>, BackendError> {
+        // For this backend `backend_path` is the display name (e.g. ":0").
+        let (conn, screen) = connect(user, backend_path)
+            .map_err(|e| BackendError::SessionNotRunning(e.to_string()))?;
+        Ok(list_client_windows(&conn, screen)?)
+    }
+
+    fn close_window(
+        &self,
+        user: &str,
+        backend_path: &str,
+        win: &WindowInfo,
+    ) -> Result<(), BackendError> {
+        let window = win
+            .id
+            .as_deref()
+            .and_then(|id| Window::from_str_radix(id.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| BackendError::Missing(format!("no X11 window id for {}", win.pid)))?;
+        let (conn, screen) = connect(user, backend_path)
+            .map_err(|e| BackendError::SessionNotRunning(e.to_string()))?;
+        Ok(close_client_window(&conn, screen, window)?)
+    }
+}
+
+/// Ask the window manager to close `window` (`_NET_CLOSE_WINDOW`), the same
+/// request a pager or taskbar sends.
+pub fn close_client_window(conn: &RustConnection, screen: usize, window: Window) -> io::Result<()> {
+    let root = conn.setup().roots[screen].root;
+    let net_close_window = conn
+        .intern_atom(false, b"_NET_CLOSE_WINDOW")
+        .map_err(io::Error::other)?
+        .reply()
+        .map_err(io::Error::other)?
+        .atom;
+
+    // data: timestamp (CurrentTime), source indication (2 = pager).
+    let event = ClientMessageEvent::new(32, window, net_close_window, [0u32, 2, 0, 0, 0]);
+    conn.send_event(
+        false,
+        root,
+        EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+        event,
+    )
+    .map_err(io::Error::other)?;
+    conn.flush().map_err(io::Error::other)?;
+    Ok(())
+}
+
+/// Connect to the X server of `user`.
+///
+/// When `display` is empty, `DISPLAY` is taken from the user's session and
+/// falls back to `:0`. The MIT cookie is read from the user's `XAUTHORITY`
+/// (or `~/.Xauthority`), since our own environment belongs to root.
+pub fn connect(user: &str, display: &str) -> io::Result<(RustConnection, usize)> {
+    let uid = user_uid(user).map_err(io::Error::other)?;
+    let display = if display.is_empty() {
+        user_session_env(uid, "DISPLAY").unwrap_or_else(|| ":0".to_string())
+    } else {
+        display.to_string()
+    };
+    let xauthority = user_session_env(uid, "XAUTHORITY")
+        .map(PathBuf::from)
+        .or_else(|| get_user_by_name(user).map(|u| u.home_dir().join(".Xauthority")));
+
+    let parsed = parse_display(Some(&display)).map_err(io::Error::other)?;
+    let screen = usize::from(parsed.screen);
+
+    let mut error = None;
+    for addr in parsed.connect_instruction() {
+        match DefaultStream::connect(&addr) {
+            Ok((stream, (family, address))) => {
+                let (auth_name, auth_data) = xauthority
+                    .as_ref()
+                    .and_then(|path| find_cookie(path, family, &address, parsed.display))
+                    .unwrap_or_default();
+                let conn = RustConnection::connect_to_stream_with_auth_info(
+                    stream, screen, auth_name, auth_data,
+                )
+                .map_err(io::Error::other)?;
+                return Ok((conn, screen));
+            }
+            Err(e) => error = Some(e),
+        }
+    }
+    Err(error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no way to connect to X display `{display}`"),
+        )
+    }))
+}
+
+/// Return every managed client window (`_NET_CLIENT_LIST`) with its pid and
+/// title. All per-window property requests are sent before the first reply is
+/// awaited, so the whole scan costs a handful of round-trips.
+pub fn list_client_windows(conn: &RustConnection, screen: usize) -> io::Result<Vec<WindowInfo>> {
+    let root = conn.setup().roots[screen].root;
+
+    let atom_names: [&[u8]; 9] = [
+        b"_NET_CLIENT_LIST",
+        b"_NET_ACTIVE_WINDOW",
+        b"_NET_WM_PID",
+        b"_NET_WM_NAME",
+        b"_NET_WM_DESKTOP",
+        b"_NET_WM_STATE",
+        b"_NET_WM_STATE_FULLSCREEN",
+        b"_NET_WM_STATE_HIDDEN",
+        b"UTF8_STRING",
+    ];
+    let atom_cookies = atom_names
+        .iter()
+        .map(|name| conn.intern_atom(false, name))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io::Error::other)?;
+    let atoms = atom_cookies
+        .into_iter()
+        .map(|cookie| cookie.reply().map(|r| r.atom))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io::Error::other)?;
+    let [
+        net_client_list,
+        net_active_window,
+        net_wm_pid,
+        net_wm_name,
+        net_wm_desktop,
+        net_wm_state,
+        net_wm_state_fullscreen,
+        net_wm_state_hidden,
+        utf8_string,
+    ] = atoms[..]
+    else {
+        unreachable!()
+    };
+
+    let clients = conn
+        .get_property(false, root, net_client_list, AtomEnum::WINDOW, 0, u32::MAX)
+        .map_err(io::Error::other)?;
+    let active = conn
+        .get_property(false, root, net_active_window, AtomEnum::WINDOW, 0, 1)
+        .map_err(io::Error::other)?;
+    let windows: Vec<Window> = clients
+        .reply()
+        .map_err(io::Error::other)?
+        .value32()
+        .map(|it| it.collect())
+        .unwrap_or_default();
+    let active: Option<Window> = active
+        .reply()
+        .ok()
+        .and_then(|r| r.value32().and_then(|mut it| it.next()));
+
+    let mut cookies = Vec::with_capacity(windows.len());
+    for &win in &windows {
+        let get = |property, kind, len| {
+            conn.get_property(false, win, property, kind, 0, len)
+                .map_err(io::Error::other)
+        };
+        cookies.push((
+            win,
+            get(net_wm_pid, AtomEnum::CARDINAL.into(), 1)?,
+            get(net_wm_name, utf8_string, u32::MAX)?,
+            get(AtomEnum::WM_NAME.into(), AtomEnum::ANY.into(), u32::MAX)?,
+            get(AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into(), u32::MAX)?,
+            get(net_wm_desktop, AtomEnum::CARDINAL.into(), 1)?,
+            get(net_wm_state, AtomEnum::ATOM.into(), u32::MAX)?,
+        ));
+    }
+
+    let mut result = Vec::new();
+    for (win, pid, net_name, name, class, desktop, state) in cookies {
+        // A window that disappeared since `_NET_CLIENT_LIST` answers with
+        // BadWindow; simply skip it.
+        let Some(pid) = pid
+            .reply()
+            .ok()
+            .and_then(|r| r.value32().and_then(|mut it| it.next()))
+        else {
+            continue;
+        };
+        let title = net_name
+            .reply()
+            .ok()
+            .filter(|r| !r.value.is_empty())
+            .or_else(|| name.reply().ok())
+            .map(|r| String::from_utf8_lossy(&r.value).into_owned())
+            .unwrap_or_default();
+        // WM_CLASS is "instance\0class\0"; the class is the stable part.
+        let app_id = class.reply().ok().and_then(|r| {
+            r.value
+                .split(|&b| b == 0)
+                .nth(1)
+                .filter(|c| !c.is_empty())
+                .map(|c| String::from_utf8_lossy(c).into_owned())
+        });
+        let workspace = desktop
+            .reply()
+            .ok()
+            .and_then(|r| r.value32().and_then(|mut it| it.next()))
+            .map(|d| d.to_string());
+        let states: Option<Vec<u32>> = state
+            .reply()
+            .ok()
+            .map(|r| r.value32().map(|it| it.collect()).unwrap_or_default());
+        let fullscreen = states
+            .as_ref()
+            .map(|s| s.contains(&net_wm_state_fullscreen));
+        let hidden = states.as_ref().map(|s| s.contains(&net_wm_state_hidden));
+
+        result.push(WindowInfo {
+            title,
+            pid,
+            id: Some(format!("{:#x}", win)),
+            app_id,
+            workspace,
+            fullscreen,
+            focused: active.map(|a| a == win),
+            hidden,
+            backend: None,
+        });
+    }
+    Ok(result)
+}
+
+/// Resolve `_NET_WM_PID` for the given X11 window ids. Windows without the
+/// property (or that vanished meanwhile) are left out of the result.
+pub fn window_pids(
+    user: &str,
+    display: &str,
+    windows: &[Window],
+) -> io::Result<HashMap<Window, u32>> {
+    let (conn, _) = connect(user, display)?;
+    let net_wm_pid = conn
+        .intern_atom(false, b"_NET_WM_PID")
+        .map_err(io::Error::other)?
+        .reply()
+        .map_err(io::Error::other)?
+        .atom;
+
+    let cookies = windows
+        .iter()
+        .map(|&win| {
+            conn.get_property(false, win, net_wm_pid, AtomEnum::CARDINAL, 0, 1)
+                .map(|cookie| (win, cookie))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io::Error::other)?;
+
+    Ok(cookies
+        .into_iter()
+        .filter_map(|(win, cookie)| {
+            let pid = cookie.reply().ok()?.value32()?.next()?;
+            Some((win, pid))
+        })
+        .collect())
+}
+
+/// Milliseconds since the last user input, via the screensaver extension.
+pub fn x11_idle_millis(user: &str, display: &str) -> io::Result<u32> {
+    let (conn, screen) = connect(user, display)?;
+    let root = conn.setup().roots[screen].root;
+    let info = conn
+        .screensaver_query_info(root)
+        .map_err(io::Error::other)?
+        .reply()
+        .map_err(io::Error::other)?;
+    Ok(info.ms_since_user_input)
+}
+
+/// Find the MIT-MAGIC-COOKIE-1 entry for the given display in an
+/// `.Xauthority` file. Returns `(auth_name, auth_data)`.
+fn find_cookie(
+    path: &Path,
+    family: Family,
+    address: &[u8],
+    display: u16,
+) -> Option<(Vec<u8>, Vec<u8>)> {
+    let content = fs::read(path).ok()?;
+    let display = display.to_string();
+    let mut buf = &content[..];
+
+    while let Some(family_raw) = read_u16(&mut buf) {
+        let entry_family = Family::from(family_raw);
+        let entry_address = read_bytes(&mut buf)?;
+        let entry_number = read_bytes(&mut buf)?;
+        let entry_name = read_bytes(&mut buf)?;
+        let entry_data = read_bytes(&mut buf)?;
+
+        let address_matches = entry_family == Family::WILD
+            || family == Family::WILD
+            || (entry_family == family && entry_address == address);
+        let number_matches = entry_number.is_empty() || entry_number == display.as_bytes();
+
+        if address_matches && number_matches && entry_name == MIT_MAGIC_COOKIE_1 {
+            return Some((entry_name, entry_data));
+        }
+    }
+    None
+}
+
+fn read_u16(buf: &mut &[u8]) -> Option<u16> {
+    let (head, rest) = buf.split_first_chunk::<2>()?;
+    *buf = rest;
+    Some(u16::from_be_bytes(*head))
+}
+
+fn read_bytes(buf: &mut &[u8]) -> Option<Vec<u8>> {
+    let len = usize::from(read_u16(buf)?);
+    if buf.len() < len {
+        return None;
+    }
+    let (head, rest) = buf.split_at(len);
+    *buf = rest;
+    Some(head.to_vec())
+}
@@ -1,5 +1,6 @@
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
@@ -27,9 +28,17 @@ pub struct Config {
     /// Regex that must match the window title
     pub title_pattern: Option<String>,
 
-    /// Which backend to use: "kdotool", "niri" or "xdotool"
-    #[serde(default = "default_backend")]
-    pub backend: String,
+    /// Regex that must match the window's app_id (Wayland) or WM_CLASS class (X11)
+    pub app_id_pattern: Option<String>,
+
+    /// Which backend to use: "kdotool", "kwin" (KDE without kdotool), "niri", "xdotool", "x11", "sway" (also for i3), "hyprland"
+    /// "wayland" (wlroots-style compositors with a foreign toplevel protocol), "proc" (no window
+    /// system; every process matching `cmd_pattern` counts), "exec" (runs `backend_path` and reads
+    /// the windows as JSON from its output), or "auto" to pick one from the
+    /// user's session on every scan. A list (e.g. `[niri, xdotool]`) queries every backend and
+    /// merges the results; `backend_path` is then ignored, so "exec" cannot be part of a list.
+    #[serde(default = "default_backend", deserialize_with = "string_or_list")]
+    pub backend: Vec<String>,
 
     #[serde(default = "default_backend_path")]
     pub backend_path: String,
@@ -41,6 +50,101 @@ pub struct Config {
     /// End time for the day
     #[serde(default = "default_time_end")]
     pub time_end: String,
+
+    /// How time is accrued: "etimes" (process run time), "focused" (only while the matched
+    /// window is focused) or "visible" (only while it is not minimised/hidden)
+    #[serde(default)]
+    pub accounting: Accounting,
+
+    /// Seconds without user input after which accounting pauses (0 disables idle detection)
+    #[serde(default)]
+    pub idle_threshold: i64,
+
+    /// Seconds a process gets to exit after SIGTERM before it is sent SIGKILL
+    #[serde(default = "default_kill_grace")]
+    pub kill_grace: u64,
+
+    /// What gets terminated: "process" (the window's pid), "tree" (it and all its descendants)
+    /// or "cgroup" (its whole systemd `app-*.scope`, falling back to "tree" outside of one)
+    #[serde(default)]
+    pub kill_scope: KillScope,
+
+    /// How a window is stopped: "kill" (terminate its process), "close" (ask the backend to
+    /// close the window first, so the app can prompt to save, and terminate only if it is still
+    /// running after `close_timeout`) or "freeze" (suspend its process, or `kill_scope`, until
+    /// it is allowed to run again)
+    #[serde(default)]
+    pub enforce_action: EnforceAction,
+
+    /// Seconds a window gets to close before its process is terminated (with "close")
+    #[serde(default = "default_close_timeout")]
+    pub close_timeout: i64,
+
+    /// What consumes the budget: "apps" (windows matching the patterns) or "session" (any time
+    /// the user's graphical session is active and unlocked, plus matched windows)
+    #[serde(default)]
+    pub limit_mode: LimitMode,
+
+    /// With `limit_mode: session`, what happens once the budget is used up or outside the
+    /// allowed hours: "lock" (`loginctl lock-session`) or "terminate" (`loginctl terminate-session`)
+    #[serde(default)]
+    pub session_action: SessionAction,
+
+    /// "enforce" (the default) or "observe": match, account and log as usual, but only log the
+    /// warnings, closes, kills and session locks that would happen
+    #[serde(default)]
+    pub mode: Mode,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Accounting {
+    #[default]
+    Etimes,
+    Focused,
+    Visible,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KillScope {
+    #[default]
+    Process,
+    Tree,
+    Cgroup,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EnforceAction {
+    #[default]
+    Kill,
+    Close,
+    Freeze,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitMode {
+    #[default]
+    Apps,
+    Session,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionAction {
+    #[default]
+    Lock,
+    Terminate,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Enforce,
+    Observe,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -51,10 +155,21 @@ struct ConfigOverride {
     pub interval: Option<u64>,
     pub cmd_pattern: Option<String>,
     pub title_pattern: Option<String>,
-    pub backend: Option<String>,
+    pub app_id_pattern: Option<String>,
+    #[serde(default, deserialize_with = "optional_string_or_list")]
+    pub backend: Option<Vec<String>>,
     pub backend_path: Option<String>,
     pub time_begin: Option<String>,
     pub time_end: Option<String>,
+    pub accounting: Option<Accounting>,
+    pub idle_threshold: Option<i64>,
+    pub kill_grace: Option<u64>,
+    pub kill_scope: Option<KillScope>,
+    pub enforce_action: Option<EnforceAction>,
+    pub close_timeout: Option<i64>,
+    pub limit_mode: Option<LimitMode>,
+    pub session_action: Option<SessionAction>,
+    pub mode: Option<Mode>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -83,8 +198,37 @@ fn default_interval() -> u64 {
     10
 }
 
-fn default_backend() -> String {
-    "kdotool".to_string()
+fn default_kill_grace() -> u64 {
+    10
+}
+
+fn default_close_timeout() -> i64 {
+    30
+}
+
+fn default_backend() -> Vec<String> {
+    vec!["kdotool".to_string()]
+}
+
+/// Accept either a single string or a list of strings.
+fn string_or_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+fn optional_string_or_list<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    string_or_list(deserializer).map(Some)
 }
 
 fn default_backend_path() -> String {
@@ -119,6 +263,9 @@ impl Config {
         if let Some(title_pattern) = config_override.title_pattern {
             self.title_pattern = Some(title_pattern);
         }
+        if let Some(app_id_pattern) = config_override.app_id_pattern {
+            self.app_id_pattern = Some(app_id_pattern);
+        }
         if let Some(backend) = config_override.backend {
             self.backend = backend;
         }
@@ -131,15 +278,47 @@ impl Config {
         if let Some(time_end) = config_override.time_end {
             self.time_end = time_end;
         }
+        if let Some(accounting) = config_override.accounting {
+            self.accounting = accounting;
+        }
+        if let Some(idle_threshold) = config_override.idle_threshold {
+            self.idle_threshold = idle_threshold;
+        }
+        if let Some(kill_grace) = config_override.kill_grace {
+            self.kill_grace = kill_grace;
+        }
+        if let Some(kill_scope) = config_override.kill_scope {
+            self.kill_scope = kill_scope;
+        }
+        if let Some(enforce_action) = config_override.enforce_action {
+            self.enforce_action = enforce_action;
+        }
+        if let Some(close_timeout) = config_override.close_timeout {
+            self.close_timeout = close_timeout;
+        }
+        if let Some(limit_mode) = config_override.limit_mode {
+            self.limit_mode = limit_mode;
+        }
+        if let Some(session_action) = config_override.session_action {
+            self.session_action = session_action;
+        }
+        if let Some(mode) = config_override.mode {
+            self.mode = mode;
+        }
     }
 }
 
 pub fn load_config(path: &PathBuf) -> Result<Config> {
+    load_config_for(path, chrono::Local::now().date_naive())
+}
+
+/// Load the config as it applies on `date` (picks that weekday's override).
+pub fn load_config_for(path: &PathBuf, date: NaiveDate) -> Result<Config> {
     let content = fs::read_to_string(path)?;
     let config = match serde_yaml::from_str::<ConfigFile>(&content)? {
         ConfigFile::Flat(config) => config,
         ConfigFile::Scheduled(mut scheduled) => {
-            let today = chrono::Local::now().format("%A").to_string();
+            let today = date.format("%A").to_string();
             if let Some(day_override) = scheduled.days.remove(&today) {
                 scheduled.default.apply_override(day_override);
             }
@@ -147,12 +326,29 @@ pub fn load_config(path: &PathBuf) -> Result<Config> {
         }
     };
 
-    // Validate that at least one pattern is provided
-    if config.cmd_pattern.is_none() && config.title_pattern.is_none() {
+    // Validate that at least one pattern is provided, unless only the
+    // session time is limited
+    if config.limit_mode != LimitMode::Session
+        && config.cmd_pattern.is_none()
+        && config.title_pattern.is_none()
+        && config.app_id_pattern.is_none()
+    {
         anyhow::bail!(
-            "At least one of 'cmd_pattern' or 'title_pattern' must be specified in config"
+            "At least one of 'cmd_pattern', 'title_pattern' or 'app_id_pattern' must be specified in config"
         );
     }
 
+    if config.backend.is_empty() {
+        anyhow::bail!("'backend' must name at least one backend");
+    }
+    if config.backend.len() > 1
+        && config
+            .backend
+            .iter()
+            .any(|backend| backend.eq_ignore_ascii_case("exec"))
+    {
+        anyhow::bail!("'exec' needs 'backend_path' and cannot be used in a list of backends");
+    }
+
     Ok(config)
 }
@@ -0,0 +1,289 @@
+use regex::Regex;
+use std::collections::HashSet;
+use std::io;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::actions::{Actions, KillPolicy, LoggedActions};
+use crate::misc::{process_command, user_pids};
+use crate::process::ProcessHandle;
+
+/// How often `/proc` is scanned when the proc connector is not available.
+const PROC_POLL: Duration = Duration::from_millis(100);
+
+// From <linux/connector.h> and <linux/cn_proc.h>.
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_EXEC: u32 = 2;
+const NLMSG_HDRLEN: usize = 16;
+const CN_MSG_LEN: usize = 20;
+
+/// What the launch watcher blocks right now; the monitor updates it on
+/// every scan.
+#[derive(Debug, Clone)]
+pub struct LaunchPolicy {
+    pub uid: u32,
+    pub cmd_rx: Regex,
+    /// Why launches are blocked, for the log.
+    pub reason: String,
+    pub kill: KillPolicy,
+    /// Only log what would be terminated.
+    pub observe: bool,
+}
+
+/// `None` while launches are allowed.
+pub type SharedLaunchPolicy = Arc<Mutex<Option<LaunchPolicy>>>;
+
+/// Watch every exec on the machine and terminate the processes of the
+/// monitored user whose command line matches while the policy blocks them,
+/// before they get to open a window. Uses the netlink proc connector (needs
+/// root) and falls back to polling `/proc`.
+pub fn spawn_launch_watcher(policy: SharedLaunchPolicy, actions: Box<dyn Actions + Send>) {
+    thread::spawn(move || {
+        match ProcConnector::open() {
+            Ok(connector) => {
+                println!("Watching launches through the proc connector");
+                loop {
+                    match connector.exec_events() {
+                        Ok(pids) => {
+                            for pid in pids {
+                                check_launch(&policy, actions.as_ref(), pid);
+                            }
+                        }
+                        Err(e) => {
+                            println!("Proc connector failed ({e}), polling /proc instead");
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => println!("Proc connector unavailable ({e}), polling /proc instead"),
+        }
+        poll_launches(&policy, actions.as_ref());
+    });
+}
+
+/// Terminate `pid` if it belongs to the blocked user and matches.
+fn check_launch(policy: &SharedLaunchPolicy, actions: &dyn Actions, pid: u32) {
+    let Some(policy) = policy.lock().unwrap().clone() else {
+        return;
+    };
+    // Pinned before anything is read, so only this very process can be hit.
+    let Ok(process) = ProcessHandle::open(pid) else {
+        return;
+    };
+    if process.uid() != Some(policy.uid) {
+        return;
+    }
+    let Some(command) = process_command(pid) else {
+        return;
+    };
+    if !command.is_empty() && policy.cmd_rx.is_match(&command) {
+        println!(
+            "Blocking launch of {pid}, {}: cmd='{command}'",
+            policy.reason
+        );
+        let actions = if policy.observe {
+            &LoggedActions
+        } else {
+            actions
+        };
+        actions.terminate(&process, policy.kill, &policy.reason);
+    }
+}
+
+/// Fallback: look for new processes of the user every `PROC_POLL`.
+fn poll_launches(policy: &SharedLaunchPolicy, actions: &dyn Actions) {
+    // Processes running when the block started are left to the scans.
+    let mut seen: Option<HashSet<u32>> = None;
+    loop {
+        let uid = policy.lock().unwrap().as_ref().map(|policy| policy.uid);
+        match uid {
+            Some(uid) => {
+                let pids: HashSet<u32> = user_pids(uid).into_iter().collect();
+                if let Some(seen) = &seen {
+                    for &pid in pids.difference(seen) {
+                        check_launch(policy, actions, pid);
+                    }
+                }
+                seen = Some(pids);
+            }
+            None => seen = None,
+        }
+        thread::sleep(PROC_POLL);
+    }
+}
+
+/// Netlink socket subscribed to the kernel's process events.
+struct ProcConnector {
+    fd: libc::c_int,
+}
+
+impl ProcConnector {
+    fn open() -> io::Result<Self> {
+        // SAFETY: plain socket creation; the fd is owned by the returned value.
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_CONNECTOR,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let connector = ProcConnector { fd };
+
+        // SAFETY: sockaddr_nl is plain data, all zeroes is a valid value.
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = CN_IDX_PROC;
+        // SAFETY: addr is a valid sockaddr_nl of the given size.
+        let bound = unsafe {
+            libc::bind(
+                fd,
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if bound < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // nlmsghdr + cn_msg + PROC_CN_MCAST_LISTEN
+        let len = NLMSG_HDRLEN + CN_MSG_LEN + 4;
+        let mut msg = Vec::with_capacity(len);
+        msg.extend_from_slice(&(len as u32).to_ne_bytes()); // nlmsg_len
+        msg.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes()); // nlmsg_type
+        msg.extend_from_slice(&0u16.to_ne_bytes()); // nlmsg_flags
+        msg.extend_from_slice(&0u32.to_ne_bytes()); // nlmsg_seq
+        msg.extend_from_slice(&std::process::id().to_ne_bytes()); // nlmsg_pid
+        msg.extend_from_slice(&CN_IDX_PROC.to_ne_bytes()); // id.idx
+        msg.extend_from_slice(&CN_VAL_PROC.to_ne_bytes()); // id.val
+        msg.extend_from_slice(&0u32.to_ne_bytes()); // seq
+        msg.extend_from_slice(&0u32.to_ne_bytes()); // ack
+        msg.extend_from_slice(&4u16.to_ne_bytes()); // len
+        msg.extend_from_slice(&0u16.to_ne_bytes()); // flags
+        msg.extend_from_slice(&PROC_CN_MCAST_LISTEN.to_ne_bytes());
+        // SAFETY: msg is a valid buffer of msg.len() bytes.
+        let sent = unsafe { libc::send(fd, msg.as_ptr().cast(), msg.len(), 0) };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(connector)
+    }
+
+    /// Block until the next batch of events; return the processes that
+    /// called exec.
+    fn exec_events(&self) -> io::Result<Vec<u32>> {
+        let mut buf = [0u8; 4096];
+        // SAFETY: buf is a valid, writable buffer of buf.len() bytes.
+        let received = unsafe { libc::recv(self.fd, buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if received < 0 {
+            let e = io::Error::last_os_error();
+            // Too many events at once; the next ones still arrive.
+            return match e.raw_os_error() {
+                Some(libc::ENOBUFS) | Some(libc::EINTR) => Ok(Vec::new()),
+                _ => Err(e),
+            };
+        }
+
+        Ok(parse_exec_events(&buf[..received as usize]))
+    }
+}
+
+/// The processes that called exec according to a datagram of netlink
+/// messages from the proc connector.
+fn parse_exec_events(mut data: &[u8]) -> Vec<u32> {
+    let u32_at = |data: &[u8], offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let mut pids = Vec::new();
+    while let Some(len) = u32_at(data, 0).map(|len| len as usize) {
+        if len < NLMSG_HDRLEN || len > data.len() {
+            break;
+        }
+        // proc_event: what, cpu, timestamp_ns, then for exec the pid
+        // and tgid of the process.
+        let event = data.get(NLMSG_HDRLEN + CN_MSG_LEN..len).unwrap_or_default();
+        if u32_at(event, 0) == Some(PROC_EVENT_EXEC)
+            && let Some(tgid) = u32_at(event, 20)
+        {
+            pids.push(tgid);
+        }
+        data = &data[len.next_multiple_of(4).min(data.len())..];
+    }
+    pids
+}
+
+impl Drop for ProcConnector {
+    fn drop(&mut self) {
+        // SAFETY: fd is owned by this value and closed only here.
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROC_EVENT_FORK: u32 = 1;
+
+    /// A netlink message from the proc connector carrying one proc_event
+    /// with the given event data (after what, cpu and timestamp_ns).
+    fn message(what: u32, event_data: &[u32]) -> Vec<u8> {
+        let mut event = Vec::new();
+        event.extend_from_slice(&what.to_ne_bytes());
+        event.extend_from_slice(&3u32.to_ne_bytes()); // cpu
+        event.extend_from_slice(&123_456_789u64.to_ne_bytes()); // timestamp_ns
+        for value in event_data {
+            event.extend_from_slice(&value.to_ne_bytes());
+        }
+
+        let len = NLMSG_HDRLEN + CN_MSG_LEN + event.len();
+        let mut msg = Vec::new();
+        msg.extend_from_slice(&(len as u32).to_ne_bytes()); // nlmsg_len
+        msg.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes()); // nlmsg_type
+        msg.extend_from_slice(&0u16.to_ne_bytes()); // nlmsg_flags
+        msg.extend_from_slice(&7u32.to_ne_bytes()); // nlmsg_seq
+        msg.extend_from_slice(&0u32.to_ne_bytes()); // nlmsg_pid
+        msg.extend_from_slice(&CN_IDX_PROC.to_ne_bytes()); // id.idx
+        msg.extend_from_slice(&CN_VAL_PROC.to_ne_bytes()); // id.val
+        msg.extend_from_slice(&7u32.to_ne_bytes()); // seq
+        msg.extend_from_slice(&0u32.to_ne_bytes()); // ack
+        msg.extend_from_slice(&(event.len() as u16).to_ne_bytes()); // len
+        msg.extend_from_slice(&0u16.to_ne_bytes()); // flags
+        msg.extend_from_slice(&event);
+        assert_eq!(msg.len(), len);
+        msg.resize(len.next_multiple_of(4), 0);
+        msg
+    }
+
+    #[test]
+    fn exec_event() {
+        // A thread (pid 4243) of process 4242 called exec.
+        let msg = message(PROC_EVENT_EXEC, &[4243, 4242]);
+        assert_eq!(parse_exec_events(&msg), [4242]);
+    }
+
+    #[test]
+    fn other_events_ignored() {
+        // fork: parent pid/tgid, child pid/tgid
+        let msg = message(PROC_EVENT_FORK, &[100, 100, 4242, 4242]);
+        assert!(parse_exec_events(&msg).is_empty());
+    }
+
+    #[test]
+    fn several_messages() {
+        let mut data = message(PROC_EVENT_EXEC, &[4242, 4242]);
+        data.extend(message(PROC_EVENT_FORK, &[4242, 4242, 4300, 4300]));
+        data.extend(message(PROC_EVENT_EXEC, &[4300, 4300]));
+        // A truncated message at the end is skipped.
+        data.extend(&message(PROC_EVENT_EXEC, &[4400, 4400])[..20]);
+        assert_eq!(parse_exec_events(&data), [4242, 4300]);
+    }
+}
@@ -1,25 +1,39 @@
 use anyhow::Result;
-use chrono::NaiveTime;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
 use clap::{Parser, Subcommand};
 use regex::Regex;
 use std::{
-    collections::HashMap,
-    fs::{self, create_dir_all, File},
+    collections::{HashMap, HashSet},
+    fs::{self, File, create_dir_all},
     io::{BufRead, BufReader},
-    path::PathBuf,
-    process::Command,
+    path::{Path, PathBuf},
+    sync::Arc,
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    backend::make_lister,
-    config::load_config,
-    misc::{fmt_time, run_command, send_stop_warning},
+    actions::{Actions, KillPolicy, LoggedActions, SystemActions},
+    backend::{
+        Backend, BackendError, ReplayLister, WindowInfo, WindowLister, close_kwin_window,
+        make_lister, query_kwin_windows, resolve_backend,
+    },
+    config::{
+        Accounting, Config, EnforceAction, LimitMode, Mode, SessionAction, load_config,
+        load_config_for,
+    },
+    launch::{LaunchPolicy, SharedLaunchPolicy, spawn_launch_watcher},
+    misc::{fmt_time, user_uid},
+    process::{ProcInspector, ProcessHandle, ProcessInspector, is_running},
+    session::{graphical_session, idle_seconds, is_session_locked},
 };
+pub mod actions;
 pub mod backend;
 pub mod config;
+pub mod launch;
 pub mod misc;
+pub mod process;
+pub mod session;
 
 /// Monitor processes/windows belonging to a given user, accumulate run‑time,
 /// warn before a configurable limit and eventually terminate the process.
@@ -40,6 +54,17 @@ enum Commands {
     TimeRemaining(TimeRemainingArgs),
     /// Show effective configuration for today
     ShowConfig(ConfigArgs),
+    /// Replay a timeline of window snapshots against a config and report
+    /// when warnings and kills would fire (nothing is killed)
+    Replay(ReplayArgs),
+    /// Print the windows of the current KWin session as JSON (used by the
+    /// `kwin` backend, runs as the session's user)
+    #[command(hide = true)]
+    KwinWindows,
+    /// Close a window of the current KWin session by its internal id (used
+    /// by the `kwin` backend, runs as the session's user)
+    #[command(hide = true)]
+    KwinClose { id: String },
 }
 
 #[derive(Parser, Debug)]
@@ -51,6 +76,11 @@ struct RunArgs {
     /// Path to the persistent apps file
     #[arg(long, short = 'a', default_value = "")]
     apps_path: String,
+
+    /// Only log the warnings, kills and session locks that would happen
+    /// (same as `mode: observe` in the config)
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -71,6 +101,17 @@ struct TimeRemainingArgs {
     apps_path: String,
 }
 
+#[derive(Parser, Debug)]
+struct ReplayArgs {
+    /// Path to the YAML configuration file
+    #[arg(long, short = 'c')]
+    config: String,
+
+    /// Path to the timeline (YAML or JSON list of snapshots)
+    #[arg(long, short = 't')]
+    timeline: String,
+}
+
 #[derive(Parser, Debug)]
 struct ConfigArgs {
     /// Path to the YAML configuration file
@@ -93,10 +134,10 @@ fn load_apps(path: &PathBuf) -> Result<HashMap<String, i64>> {
     for line in BufReader::new(f).lines() {
         let l = line?;
         let mut parts = l.splitn(2, ' ');
-        if let (Some(key), Some(val_str)) = (parts.next(), parts.next()) {
-            if let Ok(val) = val_str.parse::<i64>() {
-                map.insert(key.to_string(), val);
-            }
+        if let (Some(key), Some(val_str)) = (parts.next(), parts.next())
+            && let Ok(val) = val_str.parse::<i64>()
+        {
+            map.insert(key.to_string(), val);
         }
     }
     Ok(map)
@@ -136,6 +177,59 @@ fn parse_key(key: &str) -> Option<(String, i64, String)> {
     }
 }
 
+/// Wall-clock drift between scans that is still not considered a suspend.
+const SUSPEND_SLACK_SECS: i64 = 30;
+
+/// Longest pause between scans while the session must stay locked, so that
+/// unlocking it does not last.
+const RELOCK_POLL_SECS: u64 = 2;
+
+/// Kinds of keys that record periods to cut out of the day's total.
+const GAP_KINDS: [&str; 4] = ["idle", "lock", "sleep", "frozen"];
+
+fn parse_gap_key(key: &str) -> Option<(i64, String)> {
+    // Returns (start_epoch, date_str) if the key is a gap record
+    let mut parts = key.split(':');
+
+    // Expected layout: <kind> : <start_epoch> : <date>
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(kind), Some(start_str), Some(date)) if GAP_KINDS.contains(&kind) => {
+            Some((start_str.parse().ok()?, date.to_string()))
+        }
+        _ => None,
+    }
+}
+
+fn parse_session_key(key: &str) -> Option<(i64, String)> {
+    // Returns (start_epoch, date_str) if the key records session time
+    let mut parts = key.split(':');
+
+    // Expected layout: session : <start_epoch> : <date>
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("session"), Some(start_str), Some(date)) => {
+            Some((start_str.parse().ok()?, date.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Processes frozen by `enforce_action: freeze`, persisted as
+/// `frozen-pid:<pid>:<start_ticks> <since>` so they are resumed after a
+/// restart too. Returns (pid, start_ticks, since).
+fn frozen_pids(apps: &HashMap<String, i64>) -> Vec<(u32, u64, i64)> {
+    apps.iter()
+        .filter_map(|(key, &since)| {
+            let mut parts = key.split(':');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("frozen-pid"), Some(pid), Some(started)) => {
+                    Some((pid.parse().ok()?, started.parse().ok()?, since))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
 fn merge_intervals(mut intervals: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
     if intervals.is_empty() {
         return intervals;
@@ -162,31 +256,308 @@ fn merge_intervals(mut intervals: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
     merged
 }
 
-fn sum_seconds_for_today(apps: &HashMap<String, i64>) -> i64 {
-    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+/// Cut the `gaps` out of `intervals`. Both must be sorted and merged.
+fn subtract_intervals(intervals: &[(i64, i64)], gaps: &[(i64, i64)]) -> Vec<(i64, i64)> {
+    let mut result = Vec::with_capacity(intervals.len());
+    for &(s, e) in intervals {
+        let mut cur = s;
+        for &(gs, ge) in gaps {
+            if ge <= cur || gs >= e {
+                continue;
+            }
+            if gs > cur {
+                result.push((cur, gs));
+            }
+            cur = ge;
+            if cur >= e {
+                break;
+            }
+        }
+        if cur < e {
+            result.push((cur, e));
+        }
+    }
+    result
+}
+
+fn sum_seconds_for_today(apps: &HashMap<String, i64>, now: DateTime<Local>) -> i64 {
+    let today_date = now.date_naive();
+    let today = today_date.format("%Y-%m-%d").to_string();
+    // Time before midnight (e.g. a game left open overnight) belongs to
+    // yesterday.
+    let day_start = today_date
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|t| t.timestamp())
+        .unwrap_or(i64::MIN);
 
     let mut intervals: Vec<(i64, i64)> = Vec::new();
+    let mut gaps: Vec<(i64, i64)> = Vec::new();
 
     for (key, &etime) in apps.iter() {
-        if !key.starts_with("app:") {
+        if let Some((start_epoch, date_part)) = parse_gap_key(key) {
+            if date_part == today {
+                gaps.push((start_epoch, start_epoch.saturating_add(etime)));
+            }
             continue;
         }
 
-        // Parse the key – we need app name, start epoch, and the date part
-        if let Some((_app, start_epoch, date_part)) = parse_key(key) {
-            if date_part == today {
-                // Build the interval: [start, start + etime)
-                let end = start_epoch.saturating_add(etime);
-                intervals.push((start_epoch, end));
+        let parsed = if key.starts_with("app:") {
+            // Parse the key – we need app name, start epoch, and the date part
+            parse_key(key).map(|(_app, start_epoch, date_part)| (start_epoch, date_part))
+        } else {
+            // Session time (`limit_mode: session`) counts like an app; the
+            // apps used during it overlap and are merged.
+            parse_session_key(key)
+        };
+
+        if let Some((start_epoch, date_part)) = parsed
+            && date_part == today
+        {
+            // Build the interval: [start, start + etime)
+            let end = start_epoch.saturating_add(etime);
+            if end > day_start {
+                intervals.push((start_epoch.max(day_start), end));
             }
         }
     }
 
-    let merged = merge_intervals(intervals);
+    let merged = subtract_intervals(&merge_intervals(intervals), &merge_intervals(gaps));
 
     merged.iter().map(|&(s, e)| e - s).sum()
 }
 
+/// Persist the ongoing `<kind>:<since>:<date>` gap, extended up to now.
+fn record_gap(state: &mut MonitorState, kind: &str, since: i64) {
+    let now = chrono::Local::now();
+    let today = now.format("%Y-%m-%d").to_string();
+    state
+        .apps
+        .insert(format!("{kind}:{since}:{today}"), now.timestamp() - since);
+    state.save();
+}
+
+/// Record the ongoing idle period as an `idle:<start>:<date>` gap once the
+/// user has not touched the input devices for `threshold` seconds.
+fn track_idle(user: &str, threshold: i64, state: &mut MonitorState) {
+    let idle = idle_seconds(user).unwrap_or(0);
+    if idle < threshold {
+        if state.idle_since.take().is_some() {
+            println!("User is back, resuming accounting");
+        }
+        return;
+    }
+
+    let since = match state.idle_since {
+        Some(since) => since,
+        None => {
+            println!("User idle for {}, pausing accounting", fmt_time(idle));
+            *state
+                .idle_since
+                .insert(chrono::Local::now().timestamp() - idle)
+        }
+    };
+    record_gap(state, "idle", since);
+}
+
+/// Record the time the session stays locked as a `lock:<start>:<date>` gap.
+fn track_lock(user: &str, state: &mut MonitorState) {
+    if !is_session_locked(user) {
+        if state.locked_since.take().is_some() {
+            println!("Session unlocked, resuming accounting");
+        }
+        return;
+    }
+
+    let since = match state.locked_since {
+        Some(since) => since,
+        None => {
+            println!("Session locked, pausing accounting");
+            *state.locked_since.insert(chrono::Local::now().timestamp())
+        }
+    };
+    record_gap(state, "lock", since);
+}
+
+/// Detect a suspend since the previous scan and record it as a
+/// `sleep:<start>:<date>` gap. `Instant` does not advance while suspended,
+/// so the wall clock running ahead of it is the time spent asleep.
+fn track_suspend(state: &mut MonitorState) {
+    let now = Instant::now();
+    let now_epoch = chrono::Local::now().timestamp();
+
+    if let Some((last, last_epoch)) = state.last_scan {
+        let awake = now.duration_since(last).as_secs() as i64;
+        let asleep = (now_epoch - last_epoch) - awake;
+        if asleep > SUSPEND_SLACK_SECS {
+            println!("Machine was suspended for {}", fmt_time(asleep));
+            let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+            state
+                .apps
+                .insert(format!("sleep:{last_epoch}:{today}"), asleep);
+            state.save();
+        }
+    }
+    state.last_scan = Some((now, now_epoch));
+}
+
+/// Resume frozen processes once they may run again (time was granted, a new
+/// day or the allowed hours started, `enforce_action` changed or observe
+/// mode is on), and keep the time they spend frozen out of the day's total.
+fn track_frozen(state: &mut MonitorState, rules: &Rules, observe: bool, actions: &dyn Actions) {
+    let frozen = frozen_pids(&state.apps);
+    if frozen.is_empty() {
+        return;
+    }
+
+    let now = chrono::Local::now();
+    let now_epoch = now.timestamp();
+    let (today_begin_epoch, today_end_epoch) = rules.day_bounds(now.date_naive());
+    let blocked = rules.enforce_action == EnforceAction::Freeze
+        && !observe
+        && (today_begin_epoch > now_epoch
+            || now_epoch > today_end_epoch
+            || sum_seconds_for_today(&state.apps, now) >= rules.limit);
+
+    // Also covers the time up to a resume, and time frozen while the
+    // watchdog was not running.
+    let mut frozen_since: Option<i64> = None;
+    for (pid, started, since) in frozen {
+        if !is_running(pid, started) {
+            println!("Frozen process {pid} is gone");
+        } else {
+            frozen_since = Some(frozen_since.map_or(since, |s| s.min(since)));
+            if blocked {
+                continue;
+            }
+            actions.resume(pid, started);
+        }
+        state.apps.remove(&format!("frozen-pid:{pid}:{started}"));
+    }
+    match frozen_since {
+        Some(since) => record_gap(state, "frozen", since),
+        None => state.save(),
+    }
+}
+
+/// With `limit_mode: session`, count the time the user's graphical session
+/// is active and unlocked as a `session:<start>:<date>` entry, and lock or
+/// end the session once the budget is used up or outside the allowed hours.
+/// Returns whether the session has to stay locked.
+fn track_session(
+    user: &str,
+    state: &mut MonitorState,
+    rules: &Rules,
+    actions: &dyn Actions,
+) -> Result<bool> {
+    if graphical_session(user).is_none() {
+        if state.session_since.take().is_some() {
+            println!("Session of {user} ended");
+        }
+        return Ok(false);
+    }
+
+    let now = chrono::Local::now();
+    let now_epoch = now.timestamp();
+    let today = now.format("%Y-%m-%d").to_string();
+    let locked = state.locked_since.is_some();
+    if locked {
+        state.session_since = None;
+    } else {
+        let since = *state.session_since.get_or_insert(now_epoch);
+        state
+            .apps
+            .insert(format!("session:{since}:{today}"), now_epoch - since);
+        state.save();
+    }
+
+    let (today_begin_epoch, today_end_epoch) = rules.day_bounds(now.date_naive());
+    let total = sum_seconds_for_today(&state.apps, now);
+    let remaining = (today_end_epoch - now_epoch).min(rules.limit - total);
+
+    let reason = if today_begin_epoch > now_epoch {
+        Some(format!(
+            "before the begin time ({})",
+            fmt_time(today_begin_epoch - now_epoch)
+        ))
+    } else if now_epoch > today_end_epoch {
+        Some(format!(
+            "after the end time ({})",
+            fmt_time(now_epoch - today_end_epoch)
+        ))
+    } else if remaining < 0 {
+        Some(format!("after {} reached", fmt_time(total)))
+    } else {
+        None
+    };
+    println!(
+        "Session: used {} out of {}, remaining {}",
+        fmt_time(total),
+        fmt_time(rules.limit),
+        fmt_time(remaining.max(0))
+    );
+
+    let blocked = reason.is_some();
+    match reason {
+        // A locked session stays locked; nothing more to do.
+        Some(_) if locked && rules.session_action == SessionAction::Lock => {}
+        Some(reason) => {
+            let verb = match rules.session_action {
+                SessionAction::Lock => "Locking",
+                SessionAction::Terminate => "Terminating",
+            };
+            println!("{verb} the session of {user}, {reason}");
+            if let Err(e) = actions.end_session(user, rules.session_action) {
+                println!("Failed to end the session of {user}: {e}");
+            }
+        }
+        None if remaining < rules.warn_before && state.warned != today => {
+            // Not warned again on failure: a session without a notification
+            // server must not stop the monitor.
+            if let Err(e) = actions.warn(user, remaining) {
+                println!("Failed to warn {user}: {e}");
+            }
+            state.warned = today;
+        }
+        None => {}
+    }
+    Ok(blocked)
+}
+
+/// What the launch watcher has to block during the next interval: matching
+/// launches outside the allowed hours or once the budget is used up.
+fn launch_block(
+    user: &str,
+    state: &MonitorState,
+    rules: &Rules,
+    observe: bool,
+) -> Option<LaunchPolicy> {
+    let cmd_rx = rules.cmd_rx.clone()?;
+    let uid = user_uid(user).ok()?;
+    let now = chrono::Local::now();
+    let now_epoch = now.timestamp();
+    let (today_begin_epoch, today_end_epoch) = rules.day_bounds(now.date_naive());
+    let total = sum_seconds_for_today(&state.apps, now);
+
+    let reason = if today_begin_epoch > now_epoch {
+        "before the begin time".to_string()
+    } else if now_epoch > today_end_epoch {
+        "after the end time".to_string()
+    } else if total >= rules.limit {
+        format!("after {} reached", fmt_time(total))
+    } else {
+        return None;
+    };
+    Some(LaunchPolicy {
+        uid,
+        cmd_rx,
+        reason,
+        kill: rules.kill,
+        observe,
+    })
+}
+
 fn matches_rx(str: &str, regex_opt: &Option<Regex>) -> bool {
     match regex_opt {
         Some(re) => re.is_match(str),
@@ -194,154 +565,378 @@ fn matches_rx(str: &str, regex_opt: &Option<Regex>) -> bool {
     }
 }
 
-fn add_to_apps(
-    user: &str,
-    apps: &mut HashMap<String, i64>,
-    apps_path: &PathBuf,
-    pid: u32,
-    cmd_rx: &Option<Regex>,
-    title_rx: &Option<Regex>,
-    title: &str,
+/// Settings for a single scan, derived from the (re)loaded config.
+struct Rules {
+    cmd_rx: Option<Regex>,
+    title_rx: Option<Regex>,
+    app_id_rx: Option<Regex>,
     limit: i64,
     warn_before: i64,
-    warned: &mut String,
+    kill: KillPolicy,
+    enforce_action: EnforceAction,
+    close_timeout: i64,
     time_begin: NaiveTime,
     time_end: NaiveTime,
+    accounting: Accounting,
+    limit_mode: LimitMode,
+    session_action: SessionAction,
+}
+
+impl Rules {
+    fn from_config(config: &Config) -> Rules {
+        let cmd_regex: Option<Regex> = config.cmd_pattern.as_ref().map(|pat| {
+            Regex::new(pat).unwrap_or_else(|err| {
+                panic!("Problem compiling cmd pattern `{}`: {err:?}", pat);
+            })
+        });
+        let title_regex: Option<Regex> = config.title_pattern.as_ref().map(|pat| {
+            Regex::new(pat).unwrap_or_else(|err| {
+                panic!("Problem compiling title pattern `{}`: {err:?}", pat);
+            })
+        });
+        let app_id_regex: Option<Regex> = config.app_id_pattern.as_ref().map(|pat| {
+            Regex::new(pat).unwrap_or_else(|err| {
+                panic!("Problem compiling app_id pattern `{}`: {err:?}", pat);
+            })
+        });
+
+        let time_begin = chrono::NaiveTime::parse_from_str(&config.time_begin, "%H:%M")
+            .unwrap_or_else(|err| {
+                panic!("Parse begin time error `{}`: {err:?}", config.time_begin);
+            });
+
+        let time_end =
+            chrono::NaiveTime::parse_from_str(&config.time_end, "%H:%M").unwrap_or_else(|err| {
+                panic!("Parse end time error `{}`: {err:?}", config.time_end);
+            });
+
+        Rules {
+            cmd_rx: cmd_regex,
+            title_rx: title_regex,
+            app_id_rx: app_id_regex,
+            limit: config.limit,
+            warn_before: config.warn_before,
+            kill: KillPolicy {
+                grace: Duration::from_secs(config.kill_grace),
+                scope: config.kill_scope,
+            },
+            enforce_action: config.enforce_action,
+            close_timeout: config.close_timeout,
+            time_begin,
+            time_end,
+            accounting: config.accounting.clone(),
+            limit_mode: config.limit_mode,
+            session_action: config.session_action,
+        }
+    }
+
+    /// Begin and end of the allowed hours on `date`, as epochs.
+    fn day_bounds(&self, date: NaiveDate) -> (i64, i64) {
+        let epoch = |time| {
+            date.and_time(time)
+                .and_local_timezone(chrono::Local)
+                .single()
+                .unwrap()
+                .timestamp()
+        };
+        (epoch(self.time_begin), epoch(self.time_end))
+    }
+}
+
+/// State carried from one scan to the next.
+struct MonitorState {
+    apps: HashMap<String, i64>,
+    /// `None` when replaying, so the real apps file is left alone.
+    apps_path: Option<PathBuf>,
+    /// Date on which the warning was sent.
+    warned: String,
+    /// Start epoch of the ongoing attention run per pid (`focused` and
+    /// `visible` accounting).
+    runs: HashMap<u32, i64>,
+    /// Pids that were attended to during the current scan.
+    attended: HashSet<u32>,
+    /// Start epoch of the ongoing idle period.
+    idle_since: Option<i64>,
+    /// Start epoch of the ongoing lock.
+    locked_since: Option<i64>,
+    /// Monotonic and wall-clock time of the previous scan.
+    last_scan: Option<(Instant, i64)>,
+    /// Epoch at which each pid's window was asked to close.
+    closing: HashMap<u32, i64>,
+    /// Start epoch of the ongoing unlocked session (`limit_mode: session`).
+    session_since: Option<i64>,
+}
+
+impl MonitorState {
+    fn new(apps: HashMap<String, i64>, apps_path: Option<PathBuf>) -> Self {
+        MonitorState {
+            apps,
+            apps_path,
+            warned: String::from(""), // remember whether we already sent the warning
+            runs: HashMap::new(),
+            attended: HashSet::new(),
+            idle_since: None,
+            locked_since: None,
+            last_scan: None,
+            closing: HashMap::new(),
+            session_since: None,
+        }
+    }
+
+    fn save(&self) {
+        if let Some(path) = &self.apps_path {
+            let _ = save_apps(path, &self.apps);
+        }
+    }
+}
+
+/// Where windows and process details come from and how rules are enforced:
+/// the live system, or a replayed timeline.
+struct Host<'a> {
+    inspector: &'a dyn ProcessInspector,
+    actions: &'a dyn Actions,
+    lister: &'a dyn WindowLister,
+    backend_path: &'a str,
+}
+
+/// A matched window and the process it was inspected as.
+#[derive(Clone, Copy)]
+struct Target<'a> {
+    win: &'a WindowInfo,
+    process: &'a ProcessHandle,
+}
+
+/// Whether a window accrues time during this scan. Backends that cannot
+/// tell focus or visibility count the window, so nothing escapes the limit.
+fn is_attended(accounting: &Accounting, win: &WindowInfo) -> bool {
+    match accounting {
+        Accounting::Etimes => true,
+        Accounting::Focused => win.focused.unwrap_or(true),
+        Accounting::Visible => !win.hidden.unwrap_or(false),
+    }
+}
+
+fn add_to_apps(
+    user: &str,
+    state: &mut MonitorState,
+    rules: &Rules,
+    win: &WindowInfo,
+    now: DateTime<Local>,
+    host: &Host<'_>,
 ) -> Result<bool> {
-    // Retrieve process info via `ps`.
-    let ps_out = run_command(
-        "ps",
-        &[
-            "--no-headers",
-            "-p",
-            &pid.to_string(),
-            "-o",
-            "etimes,comm,command",
-        ],
-    )?;
-
-    // Example: "1234 bash /bin/bash -c …"
-    let mut parts = ps_out.split_whitespace();
-    let secs_str = parts
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("missing etimes from ps output"))?;
-    let comm = parts
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("missing comm from ps output"))?;
-    let command: String = parts.collect::<Vec<_>>().join(" ");
-    // The rest of the command line is ignored for our matching needs.
-    let seconds: i64 = secs_str.parse()?;
-
-    let match_cmd = if matches_rx(&command, cmd_rx) {
+    let pid = win.pid;
+    let title = win.title.as_str();
+    let app_id = win.app_id.as_deref().unwrap_or_default();
+
+    // The window may have been closed since it was listed.
+    let info = match host.inspector.inspect(pid) {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("Failed to inspect process {pid}: {e}");
+            return Ok(false);
+        }
+    };
+    let comm = info.comm.as_str();
+    let command = info.command.as_str();
+    let seconds = info.etimes;
+    let target = Target {
+        win,
+        process: &info.handle,
+    };
+
+    let match_cmd = if matches_rx(command, &rules.cmd_rx) {
         println!("Matched by cmd: {command}");
         true
     } else {
         false
     };
 
-    let match_title = if matches_rx(title, title_rx) {
+    let match_title = if matches_rx(title, &rules.title_rx) {
         println!("Matched by title: {title}");
         true
     } else {
         false
     };
 
-    if !match_cmd && !match_title {
+    let match_app_id = if matches_rx(app_id, &rules.app_id_rx) {
+        println!("Matched by app_id: {app_id}");
+        true
+    } else {
+        false
+    };
+
+    if !match_cmd && !match_title && !match_app_id {
         return Ok(false);
     }
+    let unknown = || "?".to_string();
+    let flag = |state: Option<bool>| state.map_or_else(unknown, |state| state.to_string());
+    println!(
+        "Window of {pid}: app_id={}, id={}, workspace={}, focused={}, fullscreen={}",
+        win.app_id.clone().unwrap_or_else(unknown),
+        win.id.clone().unwrap_or_else(unknown),
+        win.workspace.clone().unwrap_or_else(unknown),
+        flag(win.focused),
+        flag(win.fullscreen)
+    );
 
-    let today_date = chrono::Local::now().date_naive();
+    // Only the user's own processes are ever enforced on.
+    if let Some(uid) = info.handle.uid()
+        && user_uid(user).is_ok_and(|user_uid| user_uid != uid)
+    {
+        println!("Skipping process {pid}, it is not owned by {user}");
+        return Ok(false);
+    }
+
+    if frozen_pids(&state.apps)
+        .iter()
+        .any(|&(frozen, _, _)| frozen == pid)
+    {
+        println!("Process {pid} is frozen");
+        return Ok(true);
+    }
+
+    let today_date = now.date_naive();
     let today = today_date.format("%Y-%m-%d").to_string();
-    let now_epoch = chrono::Local::now().timestamp();
-    let start_at = now_epoch.saturating_sub(seconds);
-    let today_begin_epoch = today_date
-        .and_time(time_begin)
-        .and_local_timezone(chrono::Local)
-        .single()
-        .unwrap()
-        .timestamp();
-    let today_end_epoch = today_date
-        .and_time(time_end)
-        .and_local_timezone(chrono::Local)
-        .single()
-        .unwrap()
-        .timestamp();
+    let now_epoch = now.timestamp();
+    let (today_begin_epoch, today_end_epoch) = rules.day_bounds(today_date);
 
     if today_begin_epoch > now_epoch {
-        println!(
-            "Killing {pid}, before the begin time ({}): cmd='{comm}', title='{title}'",
+        let reason = format!(
+            "before the begin time ({})",
             fmt_time(today_begin_epoch - now_epoch)
         );
-        let _ = Command::new("kill")
-            .arg("-TERM")
-            .arg(pid.to_string())
-            .status();
+        println!("Stopping {pid}, {reason}: cmd='{comm}', title='{title}', app_id='{app_id}'");
+        enforce(user, state, rules, &target, now_epoch, &reason, host);
         return Ok(true);
     } else if now_epoch > today_end_epoch {
-        println!(
-            "Killing {pid}, after the end time ({}): cmd='{comm}', title='{title}'",
+        let reason = format!(
+            "after the end time ({})",
             fmt_time(now_epoch - today_end_epoch)
         );
-        let _ = Command::new("kill")
-            .arg("-TERM")
-            .arg(pid.to_string())
-            .status();
+        println!("Stopping {pid}, {reason}: cmd='{comm}', title='{title}', app_id='{app_id}'");
+        enforce(user, state, rules, &target, now_epoch, &reason, host);
         return Ok(true);
     }
 
-    // Build a deterministic key: "app:<comm>:<pid>:<epoch>:<YYYY‑MM‑DD>"
-    let key = format!("app:{comm}:{pid}:{start_at}:{today}");
-
-    let seconds_per_key = match apps.get_mut(&key) {
-        None => {
-            // No existing entry – just store the incoming seconds.
-            apps.insert(key.clone(), seconds);
-            seconds
-        }
-        Some(old_seconds) => {
-            // There is already a value. Compute the delta and add it.
-            let delta = seconds.saturating_sub(*old_seconds);
-            *old_seconds = old_seconds.saturating_add(delta);
-            *old_seconds
-        }
+    // With `focused`/`visible` accounting only attended scans count: the
+    // interval starts when the current attention run started.
+    let accrued = if !is_attended(&rules.accounting, win) {
+        None
+    } else if rules.accounting == Accounting::Etimes {
+        Some((now_epoch.saturating_sub(seconds), seconds))
+    } else {
+        state.attended.insert(pid);
+        let run_start = *state.runs.entry(pid).or_insert(now_epoch);
+        Some((run_start, now_epoch - run_start))
     };
 
-    let total = sum_seconds_for_today(apps);
-    let _ = save_apps(apps_path, apps);
+    let apps = &mut state.apps;
+    let accrued_key = accrued.map(|(start_at, seconds)| {
+        // Build a deterministic key: "app:<comm>:<pid>:<epoch>:<YYYY‑MM‑DD>"
+        let key = format!("app:{comm}:{pid}:{start_at}:{today}");
+
+        let seconds_per_key = match apps.get_mut(&key) {
+            None => {
+                // No existing entry – just store the incoming seconds.
+                apps.insert(key.clone(), seconds);
+                seconds
+            }
+            Some(old_seconds) => {
+                // There is already a value. Compute the delta and add it.
+                let delta = seconds.saturating_sub(*old_seconds);
+                *old_seconds = old_seconds.saturating_add(delta);
+                *old_seconds
+            }
+        };
+        (key, seconds_per_key)
+    });
+
+    let total = sum_seconds_for_today(apps, now);
+    state.save();
 
+    let limit = rules.limit;
     let remaining = if (today_end_epoch - now_epoch) < (limit - total) {
         today_end_epoch - now_epoch
     } else {
         limit - total
     };
 
+    let app_desc = match &accrued_key {
+        Some((key, seconds_per_key)) => format!("{key} = {}", fmt_time(*seconds_per_key)),
+        None => format!("{comm}:{pid} not attended"),
+    };
     println!(
-        "App[{key} = {}]: Used {} out of {}, remaining {}",
-        fmt_time(seconds_per_key),
+        "App[{app_desc}]: Used {} out of {}, remaining {}",
         fmt_time(total),
         fmt_time(limit),
-        fmt_time(remaining)
+        fmt_time(remaining.max(0))
     );
 
     // Warning / killing logic.
-    if remaining < warn_before && *warned != today {
-        send_stop_warning(user, remaining)?;
-        *warned = today;
+    if remaining < rules.warn_before && state.warned != today {
+        if let Err(e) = host.actions.warn(user, remaining) {
+            println!("Failed to warn {user}: {e}");
+        }
+        state.warned = today;
     } else if remaining < 0 {
-        println!(
-            "Killing {pid}, after {} reached: cmd='{comm}', title='{title}'",
-            fmt_time(total)
-        );
-        // Fire SIGTERM; ignore errors (process may already be gone).
-        let _ = Command::new("kill")
-            .arg("-TERM")
-            .arg(pid.to_string())
-            .status();
+        let reason = format!("after {} reached", fmt_time(total));
+        println!("Stopping {pid}, {reason}: cmd='{comm}', title='{title}', app_id='{app_id}'");
+        enforce(user, state, rules, &target, now_epoch, &reason, host);
     }
 
     Ok(true)
 }
 
+/// Stop `win`: terminate its process, freeze it with `enforce_action:
+/// freeze`, or with `enforce_action: close` first ask the window to close
+/// and terminate only once `close_timeout` passed.
+fn enforce(
+    user: &str,
+    state: &mut MonitorState,
+    rules: &Rules,
+    target: &Target<'_>,
+    now_epoch: i64,
+    reason: &str,
+    host: &Host<'_>,
+) {
+    let Target { win, process } = *target;
+    let pid = win.pid;
+    if rules.enforce_action == EnforceAction::Freeze {
+        if host.actions.freeze(process, rules.kill.scope, reason) {
+            let started = process.start_ticks();
+            state
+                .apps
+                .insert(format!("frozen-pid:{pid}:{started}"), now_epoch);
+            state.save();
+        }
+        return;
+    }
+    if rules.enforce_action == EnforceAction::Close {
+        match state.closing.get(&pid) {
+            None => match host
+                .actions
+                .close(host.lister, user, host.backend_path, win)
+            {
+                Ok(()) => {
+                    println!(
+                        "Asked the window of {pid} to close, terminating it in {} if still open",
+                        fmt_time(rules.close_timeout)
+                    );
+                    state.closing.insert(pid, now_epoch);
+                    return;
+                }
+                Err(e) => println!("Failed to close the window of {pid} ({e}), terminating it"),
+            },
+            Some(&asked) if now_epoch - asked < rules.close_timeout => return,
+            Some(_) => println!(
+                "The window of {pid} is still open after {}, terminating it",
+                fmt_time(rules.close_timeout)
+            ),
+        }
+    }
+    host.actions.terminate(process, rules.kill, reason);
+}
+
 fn resolve_apps_path(apps_path: &str) -> Result<PathBuf> {
     if !apps_path.is_empty() {
         Ok(PathBuf::from(apps_path))
@@ -354,6 +949,61 @@ fn resolve_apps_path(apps_path: &str) -> Result<PathBuf> {
     }
 }
 
+/// Immediate rescans after a window vanished mid-scan.
+const VANISHED_RETRIES: u32 = 2;
+
+/// Upper bound of the backoff while a backend is unavailable.
+const MAX_BACKOFF_SECS: u64 = 300;
+
+/// Upper bound of the backoff while the session is not running; kept short
+/// so that a fresh login is picked up quickly.
+const MAX_SESSION_BACKOFF_SECS: u64 = 60;
+
+/// Consecutive failed scans, so that an unavailable backend or session is
+/// logged once and polled less often instead of every interval.
+#[derive(Default)]
+struct BackendHealth {
+    failures: u32,
+    last_error: Option<String>,
+}
+
+impl BackendHealth {
+    /// Log the failure and return how long to wait before the next scan.
+    fn failed(&mut self, e: &BackendError, interval: u64) -> Duration {
+        self.failures += 1;
+        let message = e.to_string();
+        match e {
+            // Nothing will change until the admin or the user acts, so only
+            // report changes and back off exponentially.
+            BackendError::Missing(_) | BackendError::SessionNotRunning(_) => {
+                if self.last_error.as_deref() != Some(message.as_str()) {
+                    eprintln!("Error retrieving windows: {}", message);
+                }
+                self.last_error = Some(message);
+                let max = match e {
+                    BackendError::Missing(_) => MAX_BACKOFF_SECS,
+                    _ => MAX_SESSION_BACKOFF_SECS,
+                };
+                let factor = 1u64 << self.failures.min(8);
+                Duration::from_secs(interval.saturating_mul(factor).min(max.max(interval)))
+            }
+            // Possibly transient; report each one and keep the interval.
+            BackendError::WindowVanished(_) | BackendError::Parse(_) | BackendError::Other(_) => {
+                eprintln!("Error retrieving windows: {}", message);
+                self.last_error = Some(message);
+                Duration::from_secs(interval)
+            }
+        }
+    }
+
+    fn recovered(&mut self) {
+        if self.failures > 0 {
+            println!("Backend recovered after {} failed scans", self.failures);
+        }
+        *self = BackendHealth::default();
+    }
+}
+
 fn resolve_config_path(config_path: &str) -> Result<PathBuf> {
     if !config_path.is_empty() {
         Ok(PathBuf::from(config_path))
@@ -371,66 +1021,165 @@ fn run_monitor(args: RunArgs) -> Result<()> {
     let apps_path = resolve_apps_path(&args.apps_path)?;
 
     // Load existing data.
-    let mut apps = load_apps(&apps_path)?;
-
-    let mut warned = String::from(""); // remember whether we already sent the warning
+    let mut state = MonitorState::new(load_apps(&apps_path)?, Some(apps_path));
+    let system_actions = SystemActions::default();
+    let launch_policy = SharedLaunchPolicy::default();
+    spawn_launch_watcher(Arc::clone(&launch_policy), Box::new(system_actions.clone()));
+    let mut last_observe = false;
+
+    let mut last_backends = Vec::new();
+    let mut health = BackendHealth::default();
     loop {
         let config = load_config(&config_path)?;
-        let lister = make_lister(config.backend.clone());
+        let rules = Rules::from_config(&config);
 
-        let cmd_regex: Option<Regex> = config.cmd_pattern.as_ref().map(|pat| {
-            Regex::new(pat).unwrap_or_else(|err| {
-                panic!("Problem compiling cmd pattern `{}`: {err:?}", pat);
-            })
-        });
-        let title_regex: Option<Regex> = config.title_pattern.as_ref().map(|pat| {
-            Regex::new(pat).unwrap_or_else(|err| {
-                panic!("Problem compiling title pattern `{}`: {err:?}", pat);
-            })
-        });
-
-        let time_begin = chrono::NaiveTime::parse_from_str(&config.time_begin, "%H:%M")
-            .unwrap_or_else(|err| {
-                panic!("Parse begin time error `{}`: {err:?}", config.time_begin);
-            });
+        let observe = args.dry_run || config.mode == Mode::Observe;
+        if observe != last_observe {
+            if observe {
+                println!("Observe mode: warnings, kills and session locks are only logged");
+            } else {
+                println!("Enforce mode");
+            }
+            last_observe = observe;
+        }
+        let actions: &dyn Actions = if observe {
+            &LoggedActions
+        } else {
+            &system_actions
+        };
 
-        let time_end =
-            chrono::NaiveTime::parse_from_str(&config.time_end, "%H:%M").unwrap_or_else(|err| {
-                panic!("Parse end time error `{}`: {err:?}", config.time_end);
-            });
+        track_suspend(&mut state);
+        track_lock(&config.user, &mut state);
+        if config.idle_threshold > 0 {
+            track_idle(&config.user, config.idle_threshold, &mut state);
+        }
+        // Resuming is done for real even in observe mode, which must not
+        // keep anything frozen.
+        track_frozen(&mut state, &rules, observe, &system_actions);
+        *launch_policy.lock().unwrap() = launch_block(&config.user, &state, &rules, observe);
+
+        // Does not depend on a working window backend.
+        let mut max_delay = Duration::MAX;
+        if rules.limit_mode == LimitMode::Session
+            && track_session(&config.user, &mut state, &rules, actions)?
+        {
+            max_delay = Duration::from_secs(RELOCK_POLL_SECS);
+        }
 
-        match lister.list_windows(&config.user, &config.backend_path) {
+        // Resolved on every scan, so `auto` follows session switches.
+        let backends: Vec<Backend> = match config
+            .backend
+            .iter()
+            .map(|name| resolve_backend(name, &config.user))
+            .collect()
+        {
+            Ok(backends) => backends,
+            Err(e) => {
+                thread::sleep(health.failed(&e, config.interval).min(max_delay));
+                continue;
+            }
+        };
+        if last_backends != backends {
+            let names: Vec<String> = backends.iter().map(|b| b.to_string()).collect();
+            println!("Using backend: {}", names.join(", "));
+            last_backends = backends.clone();
+        }
+        let lister = make_lister(&backends, rules.cmd_rx.clone());
+        let host = Host {
+            inspector: &ProcInspector,
+            actions,
+            lister: lister.as_ref(),
+            backend_path: &config.backend_path,
+        };
+
+        let mut listed = lister.list_windows(&config.user, &config.backend_path);
+        for _ in 0..VANISHED_RETRIES {
+            if !matches!(listed, Err(BackendError::WindowVanished(_))) {
+                break;
+            }
+            listed = lister.list_windows(&config.user, &config.backend_path);
+        }
+        let mut delay = Duration::from_secs(config.interval);
+        match listed {
             Ok(windows) => {
-                for win in windows {
-                    add_to_apps(
-                        &config.user,
-                        &mut apps,
-                        &apps_path,
-                        win.pid,
-                        &cmd_regex,
-                        &title_regex,
-                        &win.title,
-                        config.limit,
-                        config.warn_before,
-                        &mut warned,
-                        time_begin,
-                        time_end,
-                    )?;
+                health.recovered();
+                let now = chrono::Local::now();
+                for win in &windows {
+                    add_to_apps(&config.user, &mut state, &rules, win, now, &host)?;
                 }
+                // Windows that closed no longer wait for their timeout.
+                state
+                    .closing
+                    .retain(|pid, _| windows.iter().any(|win| win.pid == *pid));
             }
-            Err(e) => eprintln!("Error retrieving windows: {}", e),
+            Err(e) => delay = health.failed(&e, config.interval),
         }
 
+        // Attention runs that were not extended during this scan are over.
+        let attended = std::mem::take(&mut state.attended);
+        state.runs.retain(|pid, _| attended.contains(pid));
+
         // Wait before the next scan.
-        thread::sleep(Duration::from_secs(config.interval));
+        thread::sleep(delay.min(max_delay));
+    }
+}
+
+fn run_replay(args: ReplayArgs) -> Result<()> {
+    let config_path = resolve_config_path(&args.config)?;
+    let replay = ReplayLister::load(Path::new(&args.timeline))?;
+    if replay.is_empty() {
+        anyhow::bail!("the timeline has no snapshots");
     }
+
+    let (now, total) = replay_timeline(&config_path, &replay, &LoggedActions)?;
+    println!("Used on {}: {}", now.format("%Y-%m-%d"), fmt_time(total));
+    Ok(())
+}
+
+/// Run every snapshot of `replay` through the monitor's logic; returns the
+/// time of the last one and the time used on its day.
+fn replay_timeline(
+    config_path: &PathBuf,
+    replay: &ReplayLister,
+    actions: &dyn Actions,
+) -> Result<(DateTime<Local>, i64)> {
+    // Start from an empty day and keep the real apps file untouched.
+    let mut state = MonitorState::new(HashMap::new(), None);
+    let host = Host {
+        inspector: replay,
+        actions,
+        lister: replay,
+        backend_path: "",
+    };
+
+    let mut now = chrono::Local::now();
+    for index in 0..replay.len() {
+        now = replay.seek(index);
+        // Per-day overrides follow the replayed date.
+        let config = load_config_for(config_path, now.date_naive())?;
+        let rules = Rules::from_config(&config);
+
+        println!("--- {} ---", now.format("%Y-%m-%d %H:%M:%S"));
+        let windows = replay.list_windows(&config.user, "")?;
+        for win in &windows {
+            add_to_apps(&config.user, &mut state, &rules, win, now, &host)?;
+        }
+        state
+            .closing
+            .retain(|pid, _| windows.iter().any(|win| win.pid == *pid));
+
+        let attended = std::mem::take(&mut state.attended);
+        state.runs.retain(|pid, _| attended.contains(pid));
+    }
+
+    Ok((now, sum_seconds_for_today(&state.apps, now)))
 }
 
 fn show_time_used(args: TimeUsedArgs) -> Result<()> {
     let apps_path = resolve_apps_path(&args.apps_path)?;
 
     let apps = load_apps(&apps_path)?;
-    let total = sum_seconds_for_today(&apps);
+    let total = sum_seconds_for_today(&apps, chrono::Local::now());
     println!("{}", fmt_time(total));
 
     Ok(())
@@ -456,7 +1205,7 @@ fn show_time_remaining(args: TimeRemainingArgs) -> Result<()> {
         .timestamp();
 
     let now_epoch = chrono::Local::now().timestamp();
-    let total = sum_seconds_for_today(&apps);
+    let total = sum_seconds_for_today(&apps, chrono::Local::now());
 
     let time_until_end = (today_end_epoch - now_epoch).max(0);
     let limit_remaining = (config.limit - total).max(0);
@@ -484,5 +1233,164 @@ fn main() -> Result<()> {
         Commands::TimeUsed(args) => show_time_used(args),
         Commands::TimeRemaining(args) => show_time_remaining(args),
         Commands::ShowConfig(args) => show_config(args),
+        Commands::Replay(args) => run_replay(args),
+        Commands::KwinWindows => {
+            println!("{}", query_kwin_windows()?);
+            Ok(())
+        }
+        Commands::KwinClose { id } => Ok(close_kwin_window(&id)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KillScope;
+    use chrono::TimeZone;
+
+    #[test]
+    fn gap_overlapping_start_or_end() {
+        assert_eq!(
+            subtract_intervals(&[(100, 200)], &[(50, 120)]),
+            [(120, 200)]
+        );
+        assert_eq!(
+            subtract_intervals(&[(100, 200)], &[(180, 250)]),
+            [(100, 180)]
+        );
+        assert_eq!(subtract_intervals(&[(100, 200)], &[(50, 250)]), []);
+    }
+
+    #[test]
+    fn gap_spanning_several_intervals() {
+        let intervals = [(100, 200), (300, 400), (500, 600)];
+        assert_eq!(
+            subtract_intervals(&intervals, &[(150, 550)]),
+            [(100, 150), (550, 600)]
+        );
+    }
+
+    #[test]
+    fn gap_inside_interval() {
+        assert_eq!(
+            subtract_intervals(&[(100, 200)], &[(120, 130), (150, 160)]),
+            [(100, 120), (130, 150), (160, 200)]
+        );
+        // Gaps touching the interval only at its bounds cut nothing.
+        assert_eq!(
+            subtract_intervals(&[(100, 200)], &[(50, 100), (200, 250)]),
+            [(100, 200)]
+        );
+    }
+
+    /// Records what the monitor does during a replay, and when.
+    struct RecordedActions<'a> {
+        replay: &'a ReplayLister,
+        events: std::cell::RefCell<Vec<String>>,
+    }
+
+    impl RecordedActions<'_> {
+        fn record(&self, event: String) {
+            let at = self.replay.now().format("%H:%M");
+            self.events.borrow_mut().push(format!("{at} {event}"));
+        }
+    }
+
+    impl Actions for RecordedActions<'_> {
+        fn terminate(&self, process: &ProcessHandle, _policy: KillPolicy, _reason: &str) {
+            self.record(format!("kill {}", process.pid()));
+        }
+
+        fn freeze(&self, process: &ProcessHandle, _scope: KillScope, _reason: &str) -> bool {
+            self.record(format!("freeze {}", process.pid()));
+            true
+        }
+
+        fn resume(&self, pid: u32, _start_ticks: u64) {
+            self.record(format!("resume {pid}"));
+        }
+
+        fn close(
+            &self,
+            _lister: &dyn WindowLister,
+            _user: &str,
+            _backend_path: &str,
+            win: &WindowInfo,
+        ) -> Result<(), BackendError> {
+            self.record(format!("close {}", win.pid));
+            Ok(())
+        }
+
+        fn warn(&self, _user: &str, remaining: i64) -> Result<()> {
+            self.record(format!("warn {}", fmt_time(remaining)));
+            Ok(())
+        }
+
+        fn end_session(&self, _user: &str, _action: SessionAction) -> Result<()> {
+            self.record("end session".to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn replay_warns_and_kills() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let replay = ReplayLister::load(&fixtures.join("replay_timeline.yaml")).unwrap();
+        let actions = RecordedActions {
+            replay: &replay,
+            events: Default::default(),
+        };
+
+        let (now, total) =
+            replay_timeline(&fixtures.join("replay_config.yaml"), &replay, &actions).unwrap();
+
+        // Without `elapsed`, the game has run since its first snapshot.
+        assert_eq!(now.format("%H:%M").to_string(), "11:01");
+        assert_eq!(total, 3660);
+        assert_eq!(
+            actions.events.into_inner(),
+            ["10:55 warn 5m", "11:01 kill 4242"]
+        );
+    }
+
+    fn apps(entries: &[(String, i64)]) -> HashMap<String, i64> {
+        entries.iter().cloned().collect()
+    }
+
+    #[test]
+    fn sum_with_gaps() {
+        let now = Local.with_ymd_and_hms(2024, 3, 5, 18, 0, 0).unwrap();
+        let noon = Local
+            .with_ymd_and_hms(2024, 3, 5, 12, 0, 0)
+            .unwrap()
+            .timestamp();
+        let apps = apps(&[
+            // Two apps overlapping for 10 minutes: 12:00-13:00, 12:50-13:30.
+            (format!("app:steam:10:{noon}:2024-03-05"), 3600),
+            (format!("app:game:11:{}:2024-03-05", noon + 3000), 2400),
+            // Idle 12:30-12:40, locked 13:20-14:00.
+            (format!("idle:{}:2024-03-05", noon + 1800), 600),
+            (format!("lock:{}:2024-03-05", noon + 4800), 2400),
+            // Yesterday's entries do not count.
+            (format!("app:steam:9:{}:2024-03-04", noon - 86400), 3600),
+            (format!("sleep:{noon}:2024-03-04"), 3600),
+        ]);
+        assert_eq!(sum_seconds_for_today(&apps, now), 5400 - 600 - 600);
+    }
+
+    #[test]
+    fn sum_from_before_midnight() {
+        let now = Local.with_ymd_and_hms(2024, 3, 5, 1, 0, 0).unwrap();
+        let midnight = Local
+            .with_ymd_and_hms(2024, 3, 5, 0, 0, 0)
+            .unwrap()
+            .timestamp();
+        // Started 23:30 and still running at 00:45; a gap before midnight
+        // does not change today's total either.
+        let apps = apps(&[
+            (format!("app:game:10:{}:2024-03-05", midnight - 1800), 4500),
+            (format!("idle:{}:2024-03-05", midnight - 1200), 600),
+        ]);
+        assert_eq!(sum_seconds_for_today(&apps, now), 2700);
     }
 }
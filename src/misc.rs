@@ -1,5 +1,6 @@
 use std::{
-    io,
+    fs, io,
+    os::unix::fs::MetadataExt,
     process::{Command, Stdio},
 };
 
@@ -9,7 +10,7 @@ use users::get_user_by_name;
 const NOTIFY_SEND_CMD: &str = "notify-send";
 
 pub fn run_as_user(user: &str, args: &[&str]) -> Result<String> {
-    let uid = get_user_by_name(user).unwrap().uid();
+    let uid = user_uid(user)?;
     let output = Command::new("runuser")
         .env("XDG_RUNTIME_DIR", format!("/run/user/{:?}", uid))
         .env(
@@ -28,7 +29,7 @@ pub fn run_as_user(user: &str, args: &[&str]) -> Result<String> {
             "command {:?} exited with status {}: {}",
             args,
             output.status,
-            String::from_utf8_lossy(&output.stderr).trim().to_string()
+            String::from_utf8_lossy(&output.stderr).trim()
         );
     }
     Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
@@ -53,11 +54,72 @@ pub fn run_command(cmd: &str, args: &[&str]) -> io::Result<String> {
         .output()?;
 
     if !output.status.success() {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("command `{}` exited with status {}", cmd, output.status),
-        ))
+        Err(io::Error::other(format!(
+            "command `{}` exited with status {}",
+            cmd, output.status
+        )))
     } else {
         Ok(String::from_utf8_lossy(&output.stdout).into_owned())
     }
 }
+
+pub fn user_uid(user: &str) -> Result<u32> {
+    get_user_by_name(user)
+        .map(|u| u.uid())
+        .ok_or_else(|| anyhow::anyhow!("unknown user `{user}`"))
+}
+
+/// Pids of all running processes owned by `uid`.
+pub fn user_pids(uid: u32) -> Vec<u32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let meta = entry.metadata().ok()?;
+            (meta.uid() == uid).then_some(pid)
+        })
+        .collect()
+}
+
+/// The kernel's short name (`comm`) of a process.
+pub fn process_name(pid: u32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|comm| comm.trim().to_string())
+}
+
+/// The command line of a process, arguments joined by spaces like the
+/// `command` column of `ps`.
+pub fn process_command(pid: u32) -> Option<String> {
+    let cmdline = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    Some(
+        cmdline
+            .split(|&b| b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(String::from_utf8_lossy)
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+/// Look up an environment variable from any running process owned by `uid`.
+///
+/// The watchdog runs as root, so the user's session environment (`DISPLAY`,
+/// `XAUTHORITY`, ...) has to be borrowed from one of their processes.
+pub fn user_session_env(uid: u32, name: &str) -> Option<String> {
+    let prefix = format!("{name}=");
+    for pid in user_pids(uid) {
+        let Ok(environ) = fs::read(format!("/proc/{pid}/environ")) else {
+            continue;
+        };
+        for var in environ.split(|&b| b == 0) {
+            if let Some(value) = var.strip_prefix(prefix.as_bytes()) {
+                return Some(String::from_utf8_lossy(value).into_owned());
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,293 @@
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::misc::{process_command, process_name};
+
+/// What the accounting needs to know about the process behind a window.
+#[derive(Debug)]
+pub struct ProcessInfo {
+    /// Seconds since the process started (`ps -o etimes`).
+    pub etimes: i64,
+    /// The kernel's short name (`ps -o comm`).
+    pub comm: String,
+    /// The full command line (`ps -o command`).
+    pub command: String,
+    /// The process that was inspected; rules are enforced through it.
+    pub handle: ProcessHandle,
+}
+
+/// Trait that defines the "interface" for looking up a window's process.
+pub trait ProcessInspector {
+    /// Fails with `NotFound` when the process is gone.
+    fn inspect(&self, pid: u32) -> io::Result<ProcessInfo>;
+}
+
+/* -------------------------------------------------------------------------- */
+/* Implementation reading /proc directly                                      */
+/* -------------------------------------------------------------------------- */
+pub struct ProcInspector;
+
+impl ProcessInspector for ProcInspector {
+    fn inspect(&self, pid: u32) -> io::Result<ProcessInfo> {
+        // Opened first, so everything read below belongs to this process.
+        let handle = ProcessHandle::open(pid)?;
+        let start_ticks = handle.start_ticks();
+        let uptime: f64 = fs::read_to_string("/proc/uptime")?
+            .split_whitespace()
+            .next()
+            .and_then(|secs| secs.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad /proc/uptime"))?;
+        let etimes = (uptime - start_ticks as f64 / clock_ticks() as f64).max(0.0) as i64;
+
+        Ok(ProcessInfo {
+            etimes,
+            comm: process_name(pid).ok_or_else(|| gone(pid))?,
+            command: process_command(pid).ok_or_else(|| gone(pid))?,
+            handle,
+        })
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/* A process pinned by a pidfd                                                */
+/* -------------------------------------------------------------------------- */
+/// One exact process. Signals go through a pidfd (Linux 5.3+), so they can
+/// never reach another process that got the same pid after this one exited.
+#[derive(Debug)]
+pub struct ProcessHandle {
+    pid: u32,
+    /// `None` for processes that only exist in a replay.
+    uid: Option<u32>,
+    start_ticks: u64,
+    pidfd: Option<OwnedFd>,
+}
+
+impl ProcessHandle {
+    /// Open a pidfd for `pid` and read its owner and start time.
+    pub fn open(pid: u32) -> io::Result<Self> {
+        let raw_pid = libc::pid_t::try_from(pid).map_err(io::Error::other)?;
+        // SAFETY: pidfd_open takes a pid and flags and returns a new fd.
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, raw_pid, 0) };
+        if fd < 0 {
+            let e = io::Error::last_os_error();
+            return Err(match e.raw_os_error() {
+                Some(libc::ESRCH) => gone(pid),
+                _ => e,
+            });
+        }
+        // SAFETY: the fd was just created and is owned by nothing else.
+        let pidfd = unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) };
+
+        let uid = fs::metadata(format!("/proc/{pid}"))
+            .map_err(|_| gone(pid))?
+            .uid();
+        let handle = ProcessHandle {
+            pid,
+            uid: Some(uid),
+            start_ticks: start_time_ticks(pid)?,
+            pidfd: Some(pidfd),
+        };
+        // If the process exited before /proc was read, the pid may already
+        // belong to another process; only a live pidfd proves it does not.
+        if handle.has_exited() {
+            return Err(gone(pid));
+        }
+        Ok(handle)
+    }
+
+    /// Open `pid` only if it is still the process that started at
+    /// `start_ticks`.
+    pub fn open_started(pid: u32, start_ticks: u64) -> io::Result<Self> {
+        let handle = ProcessHandle::open(pid)?;
+        if handle.start_ticks != start_ticks {
+            return Err(gone(pid));
+        }
+        Ok(handle)
+    }
+
+    /// A process that is not on this machine (replays); it cannot be
+    /// signalled.
+    pub fn detached(pid: u32) -> Self {
+        ProcessHandle {
+            pid,
+            uid: None,
+            start_ticks: 0,
+            pidfd: None,
+        }
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    pub fn start_ticks(&self) -> u64 {
+        self.start_ticks
+    }
+
+    /// Send `signal` to exactly this process (`pidfd_send_signal`).
+    pub fn signal(&self, signal: libc::c_int) -> io::Result<()> {
+        let Some(pidfd) = &self.pidfd else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("process {} is not on this machine", self.pid),
+            ));
+        };
+        // SAFETY: the pidfd is valid for the lifetime of self, and a null
+        // siginfo is allowed.
+        let sent = unsafe {
+            libc::syscall(
+                libc::SYS_pidfd_send_signal,
+                pidfd.as_raw_fd(),
+                signal,
+                std::ptr::null::<libc::siginfo_t>(),
+                0,
+            )
+        };
+        if sent == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Whether the process terminated (a pidfd turns readable on exit, even
+    /// while the process is still a zombie).
+    pub fn has_exited(&self) -> bool {
+        let Some(pidfd) = &self.pidfd else {
+            return true;
+        };
+        let mut poll = libc::pollfd {
+            fd: pidfd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: poll gets one valid pollfd and does not block.
+        let ready = unsafe { libc::poll(&mut poll, 1, 0) };
+        ready != 0
+    }
+
+    /// Another handle to the same process.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(ProcessHandle {
+            pid: self.pid,
+            uid: self.uid,
+            start_ticks: self.start_ticks,
+            pidfd: self.pidfd.as_ref().map(OwnedFd::try_clone).transpose()?,
+        })
+    }
+}
+
+/// Start time of a process in clock ticks since boot (field 22 of
+/// `/proc/<pid>/stat`).
+pub fn start_time_ticks(pid: u32) -> io::Result<u64> {
+    read_stat(pid).map(|(_, ticks)| ticks)
+}
+
+/// Whether the process that started at `start_ticks` still runs as `pid`;
+/// false once it exited (or is a zombie) or the pid was reused.
+pub fn is_running(pid: u32, start_ticks: u64) -> bool {
+    read_stat(pid).is_ok_and(|(state, ticks)| state != 'Z' && ticks == start_ticks)
+}
+
+/// All processes below `pid` (children, grandchildren, ...), found via the
+/// parent pids in `/proc/<pid>/stat`.
+pub fn descendants(pid: u32) -> Vec<u32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let parents: Vec<(u32, u32)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let child: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let stat = fs::read_to_string(format!("/proc/{child}/stat")).ok()?;
+            let (_, rest) = stat.rsplit_once(')')?;
+            let parent = rest.split_whitespace().nth(4 - 3)?.parse().ok()?;
+            Some((child, parent))
+        })
+        .collect();
+
+    let mut found = Vec::new();
+    let mut queue = vec![pid];
+    while let Some(parent) = queue.pop() {
+        for &(child, _) in parents.iter().filter(|&&(_, p)| p == parent) {
+            if !found.contains(&child) {
+                found.push(child);
+                queue.push(child);
+            }
+        }
+    }
+    found
+}
+
+/// The cgroup directory of the systemd user scope (`app-*.scope`) that
+/// `pid` runs in, if any. Session scopes are not returned: terminating one
+/// would end the whole login.
+pub fn app_scope_cgroup(pid: u32) -> Option<PathBuf> {
+    let cgroups = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    // cgroup v2 only: `0::/user.slice/.../app.slice/app-steam@1234.scope`
+    let path = cgroups.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let mut dir = PathBuf::from("/sys/fs/cgroup");
+    let mut scope = None;
+    for component in path.trim_start_matches('/').split('/') {
+        dir.push(component);
+        if component.starts_with("app-") && component.ends_with(".scope") {
+            scope = Some(dir.clone());
+        }
+    }
+    scope
+}
+
+/// Every process in the cgroup `dir` and its child cgroups.
+pub fn cgroup_pids(dir: &Path) -> Vec<u32> {
+    let mut pids: Vec<u32> = fs::read_to_string(dir.join("cgroup.procs"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|pid| pid.parse().ok())
+        .collect();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                pids.extend(cgroup_pids(&entry.path()));
+            }
+        }
+    }
+    pids
+}
+
+/// State (field 3) and start time (field 22) from `/proc/<pid>/stat`.
+fn read_stat(pid: u32) -> io::Result<(char, u64)> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).map_err(|_| gone(pid))?;
+    // `comm` (field 2) is in parentheses and may contain spaces, so count
+    // from the last `)`; the first field after it is field 3.
+    let fields: Vec<&str> = stat
+        .rsplit_once(')')
+        .map(|(_, rest)| rest.split_whitespace().collect())
+        .unwrap_or_default();
+    let state = fields.first().and_then(|state| state.chars().next());
+    let ticks = fields.get(22 - 3).and_then(|ticks| ticks.parse().ok());
+    match (state, ticks) {
+        (Some(state), Some(ticks)) => Ok((state, ticks)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("failed to parse /proc/{pid}/stat"),
+        )),
+    }
+}
+
+/// Clock ticks per second, the unit of the times in `/proc/<pid>/stat`.
+pub fn clock_ticks() -> u64 {
+    // SAFETY: sysconf has no preconditions.
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 { ticks as u64 } else { 100 }
+}
+
+fn gone(pid: u32) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("process {pid} is gone"))
+}
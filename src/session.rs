@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::io;
+
+use crate::backend::x11_idle_millis;
+use crate::config::SessionAction;
+use crate::misc::run_command;
+
+/// The subset of a logind session's properties the watchdog cares about.
+#[derive(Debug, Clone, Default)]
+pub struct SessionInfo {
+    pub id: String,
+    /// `x11`, `wayland`, `tty`, ...
+    pub kind: String,
+    /// `XDG_SESSION_DESKTOP` as registered with logind (e.g. `KDE`, `niri`).
+    pub desktop: String,
+}
+
+/// Query `loginctl show-session` for the given properties.
+fn show_session(id: &str, props: &[&str]) -> Option<HashMap<String, String>> {
+    let mut args = vec!["show-session", id];
+    for prop in props {
+        args.push("-p");
+        args.push(prop);
+    }
+    let output = run_command("loginctl", &args).ok()?;
+    Some(
+        output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    )
+}
+
+/// The user's primary graphical session (logind's `Display` session).
+pub fn graphical_session(user: &str) -> Option<SessionInfo> {
+    let id = run_command("loginctl", &["show-user", user, "-p", "Display", "--value"]).ok()?;
+    let id = id.trim();
+    if id.is_empty() {
+        return None;
+    }
+
+    let props = show_session(id, &["Type", "Desktop"])?;
+    let get = |key: &str| props.get(key).cloned().unwrap_or_default();
+    Some(SessionInfo {
+        id: id.to_string(),
+        kind: get("Type"),
+        desktop: get("Desktop"),
+    })
+}
+
+/// How long the user's graphical session has been idle, in seconds.
+///
+/// Uses logind's `IdleHint`/`IdleSinceHint` and, for X11 sessions, the
+/// screensaver extension; the larger value wins.
+pub fn idle_seconds(user: &str) -> Option<i64> {
+    let session = graphical_session(user)?;
+    let props = show_session(&session.id, &["IdleHint", "IdleSinceHint"])?;
+
+    let mut idle = None;
+    if props.get("IdleHint").map(String::as_str) == Some("yes") {
+        // IdleSinceHint is a realtime timestamp in microseconds.
+        let since = props
+            .get("IdleSinceHint")
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|&since| since > 0);
+        if let Some(since) = since {
+            idle = Some(chrono::Local::now().timestamp() - since / 1_000_000);
+        }
+    }
+
+    // Xwayland only sees input sent to X clients, so only ask plain X11.
+    if session.kind == "x11" {
+        match x11_idle_millis(user, "") {
+            Ok(ms) => idle = Some(idle.unwrap_or(0).max(i64::from(ms / 1000))),
+            Err(e) => eprintln!("failed to query X11 idle time: {}", e),
+        }
+    }
+    idle
+}
+
+/// Whether the user's graphical session is locked (logind `LockedHint`).
+pub fn is_session_locked(user: &str) -> bool {
+    graphical_session(user)
+        .and_then(|session| show_session(&session.id, &["LockedHint"]))
+        .is_some_and(|props| props.get("LockedHint").map(String::as_str) == Some("yes"))
+}
+
+/// Lock or terminate the user's graphical session through logind.
+pub fn end_session(user: &str, action: SessionAction) -> io::Result<()> {
+    let session = graphical_session(user).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{user} has no graphical session"),
+        )
+    })?;
+    let verb = match action {
+        SessionAction::Lock => "lock-session",
+        SessionAction::Terminate => "terminate-session",
+    };
+    run_command("loginctl", &[verb, &session.id])?;
+    Ok(())
+}